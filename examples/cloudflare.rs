@@ -1,10 +1,14 @@
 use clap::Parser;
 use cloudflare_dns_operator::{
-    dns::cloudflare::{
-        self,
-        cloudflare_api_request,
-        CloudflareApi,
-        DnsRecordInfo,
+    dns::{
+        cloudflare::{
+            self,
+            cloudflare_api_request,
+            CloudflareApi,
+            DnsRecordInfo,
+        },
+        provider::DnsProvider,
+        rate_limit::RateLimiter,
     },
     resources::RecordType,
 };
@@ -74,6 +78,9 @@ pub struct CreateRecordArgs {
 
     #[clap(long)]
     pub ttl: Option<i64>,
+
+    #[clap(long)]
+    pub proxied: Option<bool>,
 }
 
 #[derive(Parser)]
@@ -103,9 +110,15 @@ pub async fn run(cmd: Command) -> Result<()> {
     match cmd {
         Command::ListZones(ListZonesArgs { api_token }) => {
             let url = "https://api.cloudflare.com/client/v4/zones";
-            let records =
-                cloudflare_api_request::<Vec<serde_json::Value>, ()>(url, None, reqwest::Method::GET, api_token)
-                    .await?;
+            let records = cloudflare_api_request::<Vec<serde_json::Value>, ()>(
+                &reqwest::Client::new(),
+                &RateLimiter::default(),
+                url,
+                None,
+                reqwest::Method::GET,
+                api_token,
+            )
+            .await?;
             println!("{}", serde_json::to_string_pretty(&records)?);
         }
 
@@ -114,7 +127,7 @@ pub async fn run(cmd: Command) -> Result<()> {
             zone_identifier,
         }) => {
             let cloudflare_api = CloudflareApi::new(api_token);
-            let records = cloudflare_api.list_dns_records(zone_identifier).await?;
+            let records = cloudflare_api.list_dns_records(&zone_identifier).await?;
             for record in records {
                 let DnsRecordInfo {
                     id,
@@ -127,18 +140,45 @@ pub async fn run(cmd: Command) -> Result<()> {
             }
         }
 
-        Command::UpdateDnsRecord(_) => todo!(),
+        Command::UpdateDnsRecord(UpdateRecordArgs {
+            api_token,
+            zone_identifier,
+            record_identifier,
+            ttl,
+            content,
+        }) => {
+            let cloudflare_api = CloudflareApi::new(api_token);
+            let result = cloudflare_api
+                .update_dns_record(
+                    &zone_identifier,
+                    &record_identifier,
+                    cloudflare::DnsRecordPatch {
+                        content: Some(content),
+                        ttl,
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            println!("{}", serde_json::to_string_pretty(&result)?);
+        }
 
         Command::CreateDnsRecord(args) => {
             let cloudflare_api = CloudflareApi::new(args.api_token);
+            // e.g. "10 mail.example.com" for MX records
+            let content = cloudflare::RecordContent::parse(args.record_type, &args.content)?;
             let result = cloudflare_api
                 .create_dns_record(cloudflare::CreateRecordArgs {
                     zone: cloudflare::Zone::Name(args.zone_name),
                     name: args.name,
                     record_type: args.record_type,
-                    content: args.content,
+                    content: content.content,
+                    priority: content.priority,
+                    data: content.data,
                     comment: None,
                     ttl: args.ttl,
+                    proxied: args.proxied,
+                    tags: None,
+                    owner: None,
                 })
                 .await?;
             println!("{}", serde_json::to_string_pretty(&result)?);
@@ -156,7 +196,7 @@ pub async fn run(cmd: Command) -> Result<()> {
                 (Some(record_identifier), _) => {
                     for record_identifier in record_identifier {
                         cloudflare_api
-                            .delete_dns_record(zone_identifier.clone(), record_identifier)
+                            .delete_dns_record(&zone_identifier, &record_identifier)
                             .await?;
                     }
                 }
@@ -1,4 +1,7 @@
-use crate::resources::CloudflareDNSRecord;
+use crate::{
+    dns::cloudflare::Drift,
+    resources::CloudflareDNSRecord,
+};
 use chrono::prelude::*;
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::{
     Condition,
@@ -56,13 +59,103 @@ pub(crate) fn success_condition(current: &CloudflareDNSRecord, observed_generati
     }
 }
 
+/// Records whether the cloudflare record had drifted from the spec (e.g. was modified in the cloudflare dashboard) and
+/// got corrected.
+pub(crate) fn drift_condition(
+    current: &CloudflareDNSRecord,
+    drift: &[Drift],
+    observed_generation: Option<i64>,
+) -> Condition {
+    let conditions = current.status.as_ref().and_then(|status| status.conditions.as_ref());
+    let last_condition =
+        conditions.and_then(|conditions| conditions.iter().find(|condition| condition.type_ == "Drifted"));
+
+    let (status, reason, message) = if drift.is_empty() {
+        ("False", "InSync", "DNS record matches the spec".to_string())
+    } else {
+        let drift = drift.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+        (
+            "True",
+            "DriftCorrected",
+            format!("Corrected drifted attributes: {drift}"),
+        )
+    };
+
+    let last_transition_time = match last_condition {
+        Some(condition) if condition.status == status => condition.last_transition_time.clone(),
+        _ => Time(Utc::now()),
+    };
+
+    Condition {
+        type_: "Drifted".to_string(),
+        status: status.to_string(),
+        reason: reason.to_string(),
+        message,
+        last_transition_time,
+        observed_generation,
+    }
+}
+
+/// `current` conditions with those of the same type replaced by `updates`. Conditions of other types, such as
+/// `Drifted` when an error replaces `Ready`, are kept.
+pub(crate) fn merge_conditions(current: &CloudflareDNSRecord, updates: Vec<Condition>) -> Vec<Condition> {
+    let mut conditions = current
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.clone())
+        .unwrap_or_default();
+    for update in updates {
+        match conditions.iter_mut().find(|condition| condition.type_ == update.type_) {
+            Some(condition) => *condition = update,
+            None => conditions.push(update),
+        }
+    }
+    conditions
+}
+
 fn last_ready_condition(conditions: Option<&Vec<Condition>>) -> (bool, Option<&Condition>) {
     let (was_ready, last_condition) = conditions.map_or((true, None), |conditions| {
         let ready_cond = conditions.iter().find(|condition| condition.type_ == "Ready");
         (
-            ready_cond.map_or(false, |condition| condition.status == "True"),
+            ready_cond.is_some_and(|condition| condition.status == "True"),
             ready_cond,
         )
     });
     (was_ready, last_condition)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::CloudflareDNSRecordStatus;
+
+    #[test]
+    fn merged_conditions_keep_other_types() {
+        let mut record = CloudflareDNSRecord::new(
+            "record",
+            serde_json::from_value(serde_json::json!({
+                "name": "foo.example.com",
+                "content": { "value": "1.2.3.4" },
+                "zone": { "name": { "value": "example.com" } },
+            }))
+            .unwrap(),
+        );
+        record.status = Some(CloudflareDNSRecordStatus {
+            conditions: Some(vec![
+                success_condition(&record, Some(1)),
+                drift_condition(&record, &[], Some(1)),
+            ]),
+            ..Default::default()
+        });
+
+        let conditions = merge_conditions(
+            &record,
+            vec![error_condition(&record, "missing zone", "no zone", Some(2))],
+        );
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(conditions[0].type_, "Ready");
+        assert_eq!(conditions[0].status, "False");
+        assert_eq!(conditions[0].observed_generation, Some(2));
+        assert_eq!(conditions[1].type_, "Drifted");
+    }
+}
@@ -1,16 +1,71 @@
 use crate::{
-    dns::cloudflare::CloudflareApi,
+    credentials::{
+        self,
+        CredentialsClients,
+        RecordProvider,
+    },
+    dns::provider::DnsProvider,
     dns_check::DnsCheckSender,
+    gateways::HTTPRoute,
+    public_ip::PublicIp,
+    resources::CloudflareDNSRecord,
+};
+use kube::runtime::{
+    events::Recorder,
+    reflector::Store,
+};
+use std::{
+    collections::HashMap,
+    sync::Arc,
 };
-use std::collections::HashMap;
 use tokio::sync::Mutex;
 
 /// Holds state shared by the controller and other processes such as the DNS watcher.
 pub struct Context {
     pub client: kube::Client,
-    pub cloudflare_api: CloudflareApi,
+    /// The backend hosting the DNS records, usually [`crate::dns::cloudflare::CloudflareApi`].
+    pub dns_provider: Arc<dyn DnsProvider>,
+    /// Clients for the `CloudflareCredentials` records reference instead of using [`Context::dns_provider`].
+    pub credentials: CredentialsClients,
+    /// Publishes kubernetes events for CloudflareDNSRecord resources.
+    pub recorder: Recorder,
     pub do_dns_check: bool,
     pub dns_check_tx: DnsCheckSender,
     /// Maps CloudflareDNSRecord `{ns}:{name}` keys to DNS lookup results.
     pub dns_lookup_success: Mutex<HashMap<String, bool>>,
+    /// The public IP of the cluster, for `publicIp` contents.
+    pub public_ip: PublicIp,
+    /// All HTTPRoutes, if the controller runs with Gateway API support.
+    pub routes: Option<Store<HTTPRoute>>,
+}
+
+impl Context {
+    /// The provider to manage the records of `record` with: a client for the `CloudflareCredentials` it references, or
+    /// [`Context::dns_provider`] if it references none.
+    pub async fn dns_provider_for(&self, record: &CloudflareDNSRecord) -> eyre::Result<RecordProvider> {
+        let Some(name) = record.spec.credentials.as_deref() else {
+            return Ok(RecordProvider::unrestricted(self.dns_provider.clone()));
+        };
+        let ns = record.metadata.namespace.as_deref().unwrap_or("default");
+        credentials::lookup(&self.client, &self.credentials, ns, name).await
+    }
+
+    /// The provider the records in the status of `record` were created with, see
+    /// [`crate::resources::CloudflareDNSRecordStatus::credentials`]. If the credentials can no longer be read, e.g.
+    /// because they were deleted, the client last used with them is used.
+    pub async fn previous_dns_provider_for(&self, record: &CloudflareDNSRecord) -> eyre::Result<RecordProvider> {
+        let credentials = record.status.as_ref().and_then(|status| status.credentials.as_deref());
+        let Some(name) = credentials else {
+            return Ok(RecordProvider::unrestricted(self.dns_provider.clone()));
+        };
+        let ns = record.metadata.namespace.as_deref().unwrap_or("default");
+        match credentials::lookup(&self.client, &self.credentials, ns, name).await {
+            Ok(provider) => Ok(provider),
+            Err(err) => {
+                let api = self.credentials.cached(ns, name).ok_or(err)?;
+                warn!("unable to read CloudflareCredentials {ns}/{name}, using the client last used with them");
+                Ok(RecordProvider::unrestricted(api))
+            }
+        }
+    }
 }
@@ -0,0 +1,360 @@
+//! Cloudflare clients for `CloudflareCredentials` resources. Every credential gets its own [`CloudflareApi`], so zones
+//! and records cached for one account are never served to records managed with another one. Cloudflare limits
+//! requests per token, so all clients using the same token share one [`RateLimiter`].
+
+use crate::{
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    dns::{
+        cloudflare::CloudflareApi,
+        provider::DnsProvider,
+        rate_limit::RateLimiter,
+    },
+    resources::{
+        CloudflareCredentials,
+        CloudflareCredentialsSpec,
+        CloudflareDNSRecord,
+    },
+};
+use eyre::{
+    Context as _,
+    OptionExt as _,
+    Result,
+};
+use k8s_openapi::api::core::v1::Secret;
+use kube::{
+    core::PartialObjectMeta,
+    runtime::reflector::{
+        ObjectRef,
+        Store,
+    },
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc,
+        Mutex,
+        Weak,
+    },
+};
+
+/// Creates and keeps one [`CloudflareApi`] per `CloudflareCredentials` resource, keyed by namespace and name.
+pub struct CredentialsClients {
+    api_url: String,
+    http: reqwest::Client,
+    clients: Mutex<HashMap<(String, String), CredentialsClient>>,
+    /// Rate limiters by token, kept as long as a client uses them.
+    rate_limiters: Mutex<HashMap<String, Weak<RateLimiter>>>,
+}
+
+struct CredentialsClient {
+    api_token: String,
+    account_id: Option<String>,
+    api: Arc<CloudflareApi>,
+}
+
+impl CredentialsClients {
+    /// Clients send requests to `api_url` with `http`, like the client of the operator token.
+    pub fn new(api_url: impl AsRef<str>, http: reqwest::Client) -> Self {
+        Self {
+            api_url: api_url.as_ref().to_string(),
+            http,
+            clients: Default::default(),
+            rate_limiters: Default::default(),
+        }
+    }
+
+    /// The rate limiter of all clients using `api_token`.
+    pub fn rate_limiter(&self, api_token: &str) -> Arc<RateLimiter> {
+        let mut rate_limiters = self.rate_limiters.lock().expect("rate limiters lock");
+        if let Some(rate_limiter) = rate_limiters.get(api_token).and_then(Weak::upgrade) {
+            return rate_limiter;
+        }
+        rate_limiters.retain(|_, rate_limiter| rate_limiter.strong_count() > 0);
+        let rate_limiter = Arc::new(RateLimiter::default());
+        rate_limiters.insert(api_token.to_string(), Arc::downgrade(&rate_limiter));
+        rate_limiter
+    }
+
+    /// The client for the credentials `name` in namespace `ns`. It is kept as long as token and account stay the same,
+    /// together with its caches.
+    pub fn client(
+        &self,
+        ns: &str,
+        name: &str,
+        spec: &CloudflareCredentialsSpec,
+        api_token: String,
+    ) -> Arc<CloudflareApi> {
+        let mut clients = self.clients.lock().expect("credentials clients lock");
+        let key = (ns.to_string(), name.to_string());
+        if let Some(client) = clients.get(&key) {
+            if client.api_token == api_token && client.account_id == spec.account_id {
+                return client.api.clone();
+            }
+            info!("credentials {ns}/{name} changed, creating a new cloudflare client");
+        }
+
+        let mut api = CloudflareApi::new(api_token.clone())
+            .with_api_url(&self.api_url)
+            .with_http_client(self.http.clone())
+            .with_rate_limiter(self.rate_limiter(&api_token));
+        if let Some(account_id) = &spec.account_id {
+            api = api.with_account_id(account_id);
+        }
+        let api = Arc::new(api);
+        clients.insert(
+            key,
+            CredentialsClient {
+                api_token,
+                account_id: spec.account_id.clone(),
+                api: api.clone(),
+            },
+        );
+        api
+    }
+
+    /// The client last created for the credentials `name` in namespace `ns`, if any.
+    pub fn cached(&self, ns: &str, name: &str) -> Option<Arc<CloudflareApi>> {
+        let clients = self.clients.lock().expect("credentials clients lock");
+        clients
+            .get(&(ns.to_string(), name.to_string()))
+            .map(|client| client.api.clone())
+    }
+}
+
+/// The provider managing the records of a `CloudflareDNSRecord`, see [`crate::context::Context::dns_provider_for`].
+#[derive(Clone)]
+pub struct RecordProvider {
+    pub provider: Arc<dyn DnsProvider>,
+    /// The `allowed_zones` of the credentials, if any.
+    allowed_zones: Option<Vec<String>>,
+}
+
+impl RecordProvider {
+    /// A provider that may manage records in any of its zones.
+    pub fn unrestricted(provider: Arc<dyn DnsProvider>) -> Self {
+        Self {
+            provider,
+            allowed_zones: None,
+        }
+    }
+
+    /// Whether records may be managed in the zone with id `zone_id`. Allowed zones are matched by id or name.
+    pub async fn allows_zone(&self, zone_id: &str) -> Result<bool> {
+        let Some(allowed_zones) = &self.allowed_zones else {
+            return Ok(true);
+        };
+        if allowed_zones.iter().any(|allowed| allowed == zone_id) {
+            return Ok(true);
+        }
+        let zones = self.provider.list_zones().await?;
+        Ok(zones.iter().find(|zone| zone.id == zone_id).is_some_and(|zone| {
+            allowed_zones
+                .iter()
+                .any(|allowed| allowed.trim_end_matches('.').eq_ignore_ascii_case(&zone.name))
+        }))
+    }
+}
+
+/// The provider for the credentials `name` in namespace `ns`.
+pub async fn lookup(
+    client: &kube::Client,
+    clients: &CredentialsClients,
+    ns: &str,
+    name: &str,
+) -> Result<RecordProvider> {
+    let credentials = kube::Api::<CloudflareCredentials>::namespaced(client.clone(), ns)
+        .get(name)
+        .await
+        .wrap_err_with(|| format!("unable to read CloudflareCredentials {ns}/{name}"))?;
+    let api_token = credentials
+        .spec
+        .lookup_api_token(client, ns)
+        .await?
+        .ok_or_eyre(format!("missing API token of CloudflareCredentials {ns}/{name}"))?;
+
+    Ok(RecordProvider {
+        provider: clients.client(ns, name, &credentials.spec, api_token),
+        allowed_zones: credentials.spec.allowed_zones,
+    })
+}
+
+/// Whether `err` was caused by credentials or the secret holding their token that do not exist (anymore).
+pub fn is_not_found(err: &eyre::Report) -> bool {
+    err.chain()
+        .any(|cause| matches!(cause.downcast_ref(), Some(kube::Error::Api(response)) if response.code == 404))
+}
+
+/// Maps a change of [`CloudflareCredentials`] to all `CloudflareDNSRecord`s managed with them.
+pub fn records_using_credentials(
+    index: &DependencyIndex,
+    credentials: CloudflareCredentials,
+) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (credentials.metadata.name, credentials.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::Credentials { name, namespace })
+}
+
+/// Maps a change of a [`Secret`] to all `CloudflareDNSRecord`s managed with credentials reading their API token from
+/// it, e.g. after the token was rotated.
+pub fn records_using_token_secret(
+    index: &DependencyIndex,
+    credentials: &Store<CloudflareCredentials>,
+    secret: &PartialObjectMeta<Secret>,
+) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (&secret.metadata.name, &secret.metadata.namespace) else {
+        return Vec::new();
+    };
+    credentials
+        .state()
+        .iter()
+        .filter(|credentials| {
+            credentials.metadata.namespace.as_ref() == Some(namespace) && &credentials.spec.api_token.name == name
+        })
+        .filter_map(|credentials| credentials.metadata.name.clone())
+        .flat_map(|credentials| {
+            index.dependents(&Dependency::Credentials {
+                name: credentials,
+                namespace: namespace.clone(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::fake_api::FakeCloudflareApi;
+    use k8s_openapi::api::core::v1::SecretKeySelector;
+    use kube::runtime::{
+        reflector,
+        watcher,
+    };
+
+    fn spec(account_id: Option<&str>, allowed_zones: Option<&[&str]>) -> CloudflareCredentialsSpec {
+        CloudflareCredentialsSpec {
+            api_token: SecretKeySelector {
+                name: "cloudflare".to_string(),
+                key: "api-token".to_string(),
+                optional: None,
+            },
+            account_id: account_id.map(ToString::to_string),
+            allowed_zones: allowed_zones.map(|zones| zones.iter().map(ToString::to_string).collect()),
+        }
+    }
+
+    #[tokio::test]
+    async fn clients_are_kept_per_credentials_and_scoped_to_their_account() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        let clients = CredentialsClients::new(fake.url(), reqwest::Client::new());
+        let token = FakeCloudflareApi::TOKEN.to_string();
+
+        let team_a = clients.client("team-a", "cloudflare", &spec(Some("account1"), None), token.clone());
+        assert!(Arc::ptr_eq(
+            &team_a,
+            &clients.client("team-a", "cloudflare", &spec(Some("account1"), None), token.clone())
+        ));
+        assert_eq!(team_a.list_zones().await.unwrap().len(), 1);
+
+        // Same token, but other credentials and another account: a separate client with its own cache.
+        let team_b = clients.client("team-b", "cloudflare", &spec(Some("account2"), None), token.clone());
+        assert!(!Arc::ptr_eq(&team_a, &team_b));
+        assert!(team_b.list_zones().await.unwrap().is_empty());
+
+        // Cloudflare limits requests per token, whatever the credentials.
+        assert!(Arc::ptr_eq(
+            &clients.rate_limiter(&token),
+            &clients.rate_limiter(&token)
+        ));
+        assert!(!Arc::ptr_eq(
+            &clients.rate_limiter(&token),
+            &clients.rate_limiter("other")
+        ));
+
+        // A rotated token replaces the client.
+        let rotated = clients.client(
+            "team-a",
+            "cloudflare",
+            &spec(Some("account1"), None),
+            "other".to_string(),
+        );
+        assert!(!Arc::ptr_eq(&team_a, &rotated));
+        assert!(Arc::ptr_eq(&rotated, &clients.cached("team-a", "cloudflare").unwrap()));
+        assert!(clients.cached("team-c", "cloudflare").is_none());
+    }
+
+    #[test]
+    fn missing_credentials_or_secrets_are_not_found() {
+        let api_error = |code| {
+            kube::Error::Api(kube::core::ErrorResponse {
+                status: "Failure".to_string(),
+                message: "secrets \"cloudflare\" not found".to_string(),
+                reason: "NotFound".to_string(),
+                code,
+            })
+        };
+        let err = eyre::Report::new(api_error(404)).wrap_err("unable to read CloudflareCredentials team-a/cloudflare");
+        assert!(is_not_found(&err));
+        assert!(!is_not_found(&eyre::Report::new(api_error(403))));
+        assert!(!is_not_found(&eyre::eyre!("missing API token")));
+    }
+
+    #[test]
+    fn records_are_found_by_the_secret_of_their_token() {
+        let index = DependencyIndex::default();
+        let mut record: CloudflareDNSRecord = serde_json::from_value(serde_json::json!({
+            "apiVersion": "dns.cloudflare.com/v1alpha1",
+            "kind": "CloudflareDNSRecord",
+            "metadata": { "name": "foo", "namespace": "team-a" },
+            "spec": {
+                "name": "foo.example.com",
+                "content": { "value": "1.2.3.4" },
+                "zone": { "name": { "value": "example.com" } },
+                "credentials": "cloudflare",
+            },
+        }))
+        .unwrap();
+        index.apply_watcher_event(&watcher::Event::Apply(record.clone()));
+        record.metadata.name = Some("bar".to_string());
+        record.spec.credentials = None;
+        index.apply_watcher_event(&watcher::Event::Apply(record));
+
+        let (store, mut writer) = reflector::store();
+        let mut credentials = CloudflareCredentials::new("cloudflare", spec(None, None));
+        credentials.metadata.namespace = Some("team-a".to_string());
+        writer.apply_watcher_event(&watcher::Event::Apply(credentials));
+
+        let secret = |name: &str, namespace: &str| {
+            let mut secret = PartialObjectMeta::<Secret>::default();
+            secret.metadata.name = Some(name.to_string());
+            secret.metadata.namespace = Some(namespace.to_string());
+            secret
+        };
+        let records = records_using_token_secret(&index, &store, &secret("cloudflare", "team-a"));
+        assert_eq!(records, [ObjectRef::new("foo").within("team-a")]);
+        assert!(records_using_token_secret(&index, &store, &secret("cloudflare", "team-b")).is_empty());
+        assert!(records_using_token_secret(&index, &store, &secret("other", "team-a")).is_empty());
+    }
+
+    #[tokio::test]
+    async fn allowed_zones_match_id_or_name() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        fake.add_zone("zone2", "example.org");
+        let provider = |allowed_zones: Option<&[&str]>| RecordProvider {
+            provider: Arc::new(fake.client()),
+            allowed_zones: spec(None, allowed_zones).allowed_zones,
+        };
+
+        assert!(provider(None).allows_zone("zone2").await.unwrap());
+        assert!(provider(Some(&["zone2"])).allows_zone("zone2").await.unwrap());
+        let by_name = provider(Some(&["Example.com."]));
+        assert!(by_name.allows_zone("zone1").await.unwrap());
+        assert!(!by_name.allows_zone("zone2").await.unwrap());
+        assert!(!by_name.allows_zone("unknown").await.unwrap());
+    }
+}
@@ -0,0 +1,440 @@
+//! Keeps track of the resources `CloudflareDNSRecord`s read their content or zone from (such as services or secrets),
+//! so that a change of such a resource triggers a reconcile of every record referencing it.
+
+use crate::resources::{
+    CloudflareDNSRecord,
+    Reference,
+    StringOrService,
+    ValueOrReference,
+    ZoneNameOrId,
+};
+use k8s_openapi::api::core::v1::{
+    ConfigMap,
+    Secret,
+};
+use kube::{
+    core::PartialObjectMeta,
+    runtime::{
+        reflector::ObjectRef,
+        watcher,
+    },
+    ResourceExt as _,
+};
+use std::{
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    sync::{
+        Arc,
+        RwLock,
+    },
+};
+
+/// A resource a `CloudflareDNSRecord` depends on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dependency {
+    Service {
+        name: String,
+        namespace: String,
+    },
+    Ingress {
+        name: String,
+        namespace: String,
+    },
+    Gateway {
+        name: String,
+        namespace: String,
+    },
+    /// Any node, node contents select nodes by label.
+    Nodes,
+    /// The public IP of the cluster.
+    PublicIp,
+    ConfigMap {
+        name: String,
+        namespace: String,
+    },
+    Secret {
+        name: String,
+        namespace: String,
+    },
+    /// The `CloudflareCredentials` the record is managed with.
+    Credentials {
+        name: String,
+        namespace: String,
+    },
+}
+
+impl Dependency {
+    /// The dependencies of `record`. References without a namespace resolve to the namespace of the record.
+    pub fn of(record: &CloudflareDNSRecord) -> Vec<Dependency> {
+        let ns = record.namespace().unwrap_or_else(|| "default".to_string());
+        let mut dependencies = Vec::new();
+
+        let zone = match &record.spec.zone {
+            ZoneNameOrId::Name(it) | ZoneNameOrId::Id(it) => Dependency::of_value(it, &ns),
+        };
+
+        for source in record.spec.content_sources() {
+            let dependency = match source {
+                StringOrService::Value(_)
+                | StringOrService::Mx(_)
+                | StringOrService::Srv(_)
+                | StringOrService::Caa(_)
+                | StringOrService::Https(_)
+                | StringOrService::Svcb(_)
+                | StringOrService::Tlsa(_)
+                | StringOrService::Ds(_)
+                | StringOrService::Sshfp(_)
+                | StringOrService::Naptr(_)
+                | StringOrService::Cert(_)
+                | StringOrService::Uri(_)
+                | StringOrService::Loc(_) => continue,
+                StringOrService::Reference(reference) => Dependency::of_reference(reference, &ns),
+                StringOrService::Template(template) => {
+                    for dependency in template.dependencies(&ns) {
+                        if !dependencies.contains(&dependency) {
+                            dependencies.push(dependency);
+                        }
+                    }
+                    continue;
+                }
+                StringOrService::Service(selector) => Dependency::Service {
+                    name: selector.name.clone(),
+                    namespace: selector.namespace.clone().unwrap_or_else(|| ns.clone()),
+                },
+                StringOrService::Ingress(selector) => Dependency::Ingress {
+                    name: selector.name.clone(),
+                    namespace: selector.namespace.clone().unwrap_or_else(|| ns.clone()),
+                },
+                StringOrService::Gateway(selector) => Dependency::Gateway {
+                    name: selector.name.clone(),
+                    namespace: selector.namespace.clone().unwrap_or_else(|| ns.clone()),
+                },
+                StringOrService::Node(_) => Dependency::Nodes,
+                StringOrService::PublicIp(_) => Dependency::PublicIp,
+            };
+            if !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+        }
+
+        if let Some(zone) = zone.filter(|zone| !dependencies.contains(zone)) {
+            dependencies.push(zone);
+        }
+
+        if let Some(name) = &record.spec.credentials {
+            dependencies.push(Dependency::Credentials {
+                name: name.clone(),
+                namespace: ns,
+            });
+        }
+
+        dependencies
+    }
+
+    /// The configMap or secret a value is read from, if any. References resolve to namespace `ns`.
+    pub fn of_value(value: &ValueOrReference, ns: &str) -> Option<Dependency> {
+        match value {
+            ValueOrReference::Value(_) => None,
+            ValueOrReference::Reference(reference) => Some(Dependency::of_reference(reference, ns)),
+        }
+    }
+
+    /// The configMap or secret `reference` points to in namespace `ns`.
+    pub fn of_reference(reference: &Reference, ns: &str) -> Dependency {
+        match reference {
+            Reference::ConfigMap(selector) => Dependency::ConfigMap {
+                name: selector.name.clone(),
+                namespace: ns.to_string(),
+            },
+            Reference::Secret(selector) => Dependency::Secret {
+                name: selector.name.clone(),
+                namespace: ns.to_string(),
+            },
+        }
+    }
+}
+
+/// Maps a change of a [`ConfigMap`] to all `CloudflareDNSRecord`s reading from it. Only the metadata is watched.
+pub fn records_referencing_config_map(
+    index: &DependencyIndex,
+    config_map: PartialObjectMeta<ConfigMap>,
+) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (config_map.metadata.name, config_map.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::ConfigMap { name, namespace })
+}
+
+/// Maps a change of a [`Secret`] to all `CloudflareDNSRecord`s reading from it. Only the metadata is watched, so the
+/// controller never holds secret data it does not need.
+pub fn records_referencing_secret(
+    index: &DependencyIndex,
+    secret: PartialObjectMeta<Secret>,
+) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (secret.metadata.name, secret.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::Secret { name, namespace })
+}
+
+/// Reverse index from [`Dependency`]s to the `CloudflareDNSRecord`s referencing them. Fed by the watch events of
+/// `CloudflareDNSRecord`s, see [`DependencyIndex::apply_watcher_event`].
+#[derive(Clone, Default)]
+pub struct DependencyIndex {
+    inner: Arc<RwLock<IndexState>>,
+}
+
+#[derive(Default)]
+struct IndexState {
+    dependents: HashMap<Dependency, HashSet<ObjectRef<CloudflareDNSRecord>>>,
+    dependencies: HashMap<ObjectRef<CloudflareDNSRecord>, Vec<Dependency>>,
+    /// Records seen since the last watcher restart.
+    relisted: Option<HashSet<ObjectRef<CloudflareDNSRecord>>>,
+}
+
+impl IndexState {
+    fn insert(&mut self, record: &CloudflareDNSRecord) {
+        let obj_ref = ObjectRef::from_obj(record);
+        self.remove(&obj_ref);
+
+        let dependencies = Dependency::of(record);
+        for dependency in &dependencies {
+            self.dependents
+                .entry(dependency.clone())
+                .or_default()
+                .insert(obj_ref.clone());
+        }
+        self.dependencies.insert(obj_ref, dependencies);
+    }
+
+    fn remove(&mut self, obj_ref: &ObjectRef<CloudflareDNSRecord>) {
+        for dependency in self.dependencies.remove(obj_ref).unwrap_or_default() {
+            if let Some(dependents) = self.dependents.get_mut(&dependency) {
+                dependents.remove(obj_ref);
+                if dependents.is_empty() {
+                    self.dependents.remove(&dependency);
+                }
+            }
+        }
+    }
+}
+
+impl DependencyIndex {
+    /// Updates the index with a watch event of a `CloudflareDNSRecord`.
+    pub fn apply_watcher_event(&self, event: &watcher::Event<CloudflareDNSRecord>) {
+        let mut state = self.inner.write().expect("dependency index lock");
+        match event {
+            watcher::Event::Apply(record) => state.insert(record),
+            watcher::Event::Delete(record) => state.remove(&ObjectRef::from_obj(record)),
+            watcher::Event::Init => state.relisted = Some(HashSet::new()),
+            watcher::Event::InitApply(record) => {
+                state.insert(record);
+                if let Some(relisted) = state.relisted.as_mut() {
+                    relisted.insert(ObjectRef::from_obj(record));
+                }
+            }
+            watcher::Event::InitDone => {
+                // Records that were not listed again have been deleted while the watcher was down.
+                let relisted = state.relisted.take().unwrap_or_default();
+                let gone = state
+                    .dependencies
+                    .keys()
+                    .filter(|obj_ref| !relisted.contains(*obj_ref))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                for obj_ref in gone {
+                    state.remove(&obj_ref);
+                }
+            }
+        }
+    }
+
+    /// All records referencing `dependency`.
+    pub fn dependents(&self, dependency: &Dependency) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+        let state = self.inner.read().expect("dependency index lock");
+        state
+            .dependents
+            .get(dependency)
+            .map(|dependents| dependents.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resources::{
+        CloudflareDNSRecordSpec,
+        IngressSelector,
+        ServiceSelector,
+        TemplateContent,
+        ValueOrReference,
+        ZoneNameOrId,
+    };
+    use k8s_openapi::api::core::v1::{
+        ConfigMapKeySelector,
+        SecretKeySelector,
+    };
+
+    fn record(name: &str, ns: &str, service: &str, service_ns: Option<&str>) -> CloudflareDNSRecord {
+        let mut record = CloudflareDNSRecord::new(
+            name,
+            CloudflareDNSRecordSpec {
+                name: format!("{name}.example.com"),
+                ty: None,
+                dual_stack: None,
+                content: Some(StringOrService::Service(ServiceSelector {
+                    name: service.to_string(),
+                    namespace: service_ns.map(ToString::to_string),
+                    ..Default::default()
+                })),
+                contents: None,
+                ttl: None,
+                proxied: None,
+                comment: None,
+                tags: None,
+                zone: ZoneNameOrId::Name(ValueOrReference::Value("example.com".to_string())),
+                credentials: None,
+                ownership: None,
+            },
+        );
+        record.metadata.namespace = Some(ns.to_string());
+        record
+    }
+
+    fn service(name: &str, namespace: &str) -> Dependency {
+        Dependency::Service {
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    #[test]
+    fn records_are_found_by_the_services_they_reference() {
+        let index = DependencyIndex::default();
+        let foo = record("foo", "apps", "traefik", Some("traefik"));
+        let bar = record("bar", "traefik", "traefik", None);
+        let baz = record("baz", "apps", "nginx", None);
+        for record in [&foo, &bar, &baz] {
+            index.apply_watcher_event(&watcher::Event::Apply(record.clone()));
+        }
+
+        let mut dependents = index.dependents(&service("traefik", "traefik"));
+        dependents.sort_by_key(|obj_ref| obj_ref.name.clone());
+        assert_eq!(dependents, [ObjectRef::from_obj(&bar), ObjectRef::from_obj(&foo)]);
+        assert_eq!(index.dependents(&service("nginx", "apps")), [ObjectRef::from_obj(&baz)]);
+        assert!(index.dependents(&service("traefik", "apps")).is_empty());
+
+        // changing the reference moves the record
+        let foo = record("foo", "apps", "nginx", None);
+        index.apply_watcher_event(&watcher::Event::Apply(foo.clone()));
+        assert_eq!(
+            index.dependents(&service("traefik", "traefik")),
+            [ObjectRef::from_obj(&bar)]
+        );
+        assert_eq!(index.dependents(&service("nginx", "apps")).len(), 2);
+
+        index.apply_watcher_event(&watcher::Event::Delete(bar));
+        assert!(index.dependents(&service("traefik", "traefik")).is_empty());
+    }
+
+    #[test]
+    fn services_and_ingresses_are_distinct_dependencies() {
+        let mut record = record("foo", "apps", "web", None);
+        record.spec.contents = Some(vec![StringOrService::Ingress(IngressSelector {
+            name: "web".to_string(),
+            ..Default::default()
+        })]);
+
+        assert_eq!(
+            Dependency::of(&record),
+            [
+                service("web", "apps"),
+                Dependency::Ingress {
+                    name: "web".to_string(),
+                    namespace: "apps".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn templates_and_zones_depend_on_their_references() {
+        let mut record = record("foo", "apps", "web", None);
+        record.spec.content = Some(StringOrService::Template(TemplateContent {
+            template: "v=spf1 ip4:{{ service.mail.smtp.ipv4 }} include:{{ values.include }} -all".to_string(),
+            values: Some(
+                [(
+                    "include".to_string(),
+                    ValueOrReference::Reference(Reference::ConfigMap(ConfigMapKeySelector {
+                        name: "spf".to_string(),
+                        key: "include".to_string(),
+                        ..Default::default()
+                    })),
+                )]
+                .into(),
+            ),
+        }));
+        record.spec.zone = ZoneNameOrId::Id(ValueOrReference::Reference(Reference::Secret(SecretKeySelector {
+            name: "cloudflare".to_string(),
+            key: "zone-id".to_string(),
+            ..Default::default()
+        })));
+
+        assert_eq!(
+            Dependency::of(&record),
+            [
+                service("smtp", "mail"),
+                Dependency::ConfigMap {
+                    name: "spf".to_string(),
+                    namespace: "apps".to_string(),
+                },
+                Dependency::Secret {
+                    name: "cloudflare".to_string(),
+                    namespace: "apps".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn contents_depend_on_the_secrets_they_are_read_from() {
+        let mut record = record("foo", "apps", "web", None);
+        record.spec.content = Some(StringOrService::Reference(Reference::Secret(SecretKeySelector {
+            name: "dkim".to_string(),
+            key: "txt".to_string(),
+            ..Default::default()
+        })));
+
+        let index = DependencyIndex::default();
+        index.apply_watcher_event(&watcher::Event::Apply(record.clone()));
+
+        let mut secret = PartialObjectMeta::<Secret>::default();
+        secret.metadata.name = Some("dkim".to_string());
+        secret.metadata.namespace = Some("apps".to_string());
+        assert_eq!(
+            records_referencing_secret(&index, secret),
+            [ObjectRef::from_obj(&record)]
+        );
+    }
+
+    #[test]
+    fn records_missing_after_a_relist_are_removed() {
+        let index = DependencyIndex::default();
+        let foo = record("foo", "apps", "traefik", None);
+        let bar = record("bar", "apps", "traefik", None);
+        index.apply_watcher_event(&watcher::Event::Apply(foo.clone()));
+        index.apply_watcher_event(&watcher::Event::Apply(bar));
+
+        index.apply_watcher_event(&watcher::Event::Init);
+        index.apply_watcher_event(&watcher::Event::InitApply(foo.clone()));
+        index.apply_watcher_event(&watcher::Event::InitDone);
+
+        assert_eq!(
+            index.dependents(&service("traefik", "apps")),
+            [ObjectRef::from_obj(&foo)]
+        );
+    }
+}
@@ -1,15 +1,42 @@
-use super::util;
-use crate::resources::RecordType;
+use super::{
+    ownership::{
+        self,
+        Ownership,
+    },
+    provider::{
+        DnsProvider,
+        ZoneInfo,
+    },
+    rate_limit::{
+        self,
+        RateLimiter,
+    },
+    util,
+};
+use crate::{
+    record_data::{
+        normalize_content,
+        RecordData,
+    },
+    resources::{
+        OwnershipPolicy,
+        RecordType,
+    },
+};
+use async_trait::async_trait;
 use chrono::{
     prelude::*,
     Duration,
 };
 use eyre::{
-    bail,
     Context as _,
     Result,
 };
-use reqwest::Method;
+use reqwest::{
+    header,
+    Method,
+    StatusCode,
+};
 use serde::{
     de::DeserializeOwned,
     Deserialize,
@@ -18,7 +45,9 @@ use serde::{
 use serde_json::Value;
 use std::{
     collections::HashMap,
+    path::PathBuf,
     sync::Arc,
+    time::Duration as StdDuration,
 };
 use tokio::sync::Mutex;
 
@@ -28,13 +57,125 @@ use tokio::sync::Mutex;
 /// Wraps the cloudflare api response.
 #[derive(Debug, Serialize, Deserialize)]
 struct ApiResult<T> {
-    errors: Value,
+    #[serde(default)]
+    errors: Vec<ApiError>,
     messages: Value,
     result: T,
     result_info: Option<ApiResultInfo>,
     success: bool,
 }
 
+/// The error envelope of failed requests, which have no `result`.
+#[derive(Debug, Deserialize)]
+struct ApiErrorResult {
+    #[serde(default)]
+    errors: Vec<ApiError>,
+}
+
+/// An entry of the `errors` of a cloudflare api response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiError {
+    pub code: u32,
+    pub message: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+/// Cloudflare error codes of failed authentication, e.g. an invalid or expired token.
+const AUTHENTICATION_ERROR_CODES: [u32; 4] = [9103, 9106, 9109, 10000];
+/// Cloudflare error codes of missing zones and records.
+const NOT_FOUND_ERROR_CODES: [u32; 2] = [7003, 81044];
+/// Cloudflare error codes of records conflicting with existing ones, e.g. a CNAME next to other records of the name.
+const CONFLICT_ERROR_CODES: [u32; 4] = [81053, 81054, 81057, 81058];
+
+/// A failed cloudflare api request.
+#[derive(Debug, thiserror::Error)]
+pub enum CloudflareError {
+    #[error("cloudflare api authentication failed (status {status}): {}", join(errors))]
+    Authentication { status: StatusCode, errors: Vec<ApiError> },
+
+    #[error("cloudflare api: not found: {}", join(errors))]
+    NotFound { errors: Vec<ApiError> },
+
+    #[error("cloudflare api rate limit exceeded")]
+    RateLimited { retry_after: Option<StdDuration> },
+
+    #[error("cloudflare api rejected the request: {}", join(errors))]
+    Validation { errors: Vec<ApiError> },
+
+    #[error("cloudflare api: conflicting record: {}", join(errors))]
+    Conflict { errors: Vec<ApiError> },
+
+    #[error("cloudflare api error (status {status}): {}", join(errors))]
+    Api { status: StatusCode, errors: Vec<ApiError> },
+
+    #[error("cloudflare api request failed: {0}")]
+    Transport(#[from] reqwest::Error),
+
+    #[error("unexpected cloudflare api response: {0}")]
+    InvalidResponse(String),
+
+    #[error("unable to serialize cloudflare api request: {0}")]
+    InvalidRequest(serde_json::Error),
+}
+
+fn join(errors: &[ApiError]) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+}
+
+impl CloudflareError {
+    /// Classifies a failed response by its status and the cloudflare error codes.
+    fn from_response(status: StatusCode, errors: Vec<ApiError>, retry_after: Option<StdDuration>) -> Self {
+        let has_code = |codes: &[u32]| errors.iter().any(|error| codes.contains(&error.code));
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            CloudflareError::RateLimited { retry_after }
+        } else if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN)
+            || has_code(&AUTHENTICATION_ERROR_CODES)
+        {
+            CloudflareError::Authentication { status, errors }
+        } else if status == StatusCode::NOT_FOUND || has_code(&NOT_FOUND_ERROR_CODES) {
+            CloudflareError::NotFound { errors }
+        } else if status == StatusCode::CONFLICT || has_code(&CONFLICT_ERROR_CODES) {
+            CloudflareError::Conflict { errors }
+        } else if status.is_client_error() || status.is_success() {
+            CloudflareError::Validation { errors }
+        } else {
+            CloudflareError::Api { status, errors }
+        }
+    }
+
+    /// The cloudflare error codes, if cloudflare reported any.
+    pub fn codes(&self) -> Vec<u32> {
+        match self {
+            CloudflareError::Authentication { errors, .. }
+            | CloudflareError::NotFound { errors }
+            | CloudflareError::Validation { errors }
+            | CloudflareError::Conflict { errors }
+            | CloudflareError::Api { errors, .. } => errors.iter().map(|error| error.code).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The reason of a status condition reporting this error.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            CloudflareError::Authentication { .. } => "cloudflare authentication failed",
+            CloudflareError::NotFound { .. } => "cloudflare resource not found",
+            CloudflareError::RateLimited { .. } => "cloudflare rate limited",
+            CloudflareError::Validation { .. } => "invalid record",
+            CloudflareError::Conflict { .. } => "conflicting record",
+            CloudflareError::Api { .. }
+            | CloudflareError::Transport(_)
+            | CloudflareError::InvalidResponse(_)
+            | CloudflareError::InvalidRequest(_) => "cloudflare error",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct ApiResultInfo {
     count: usize,
@@ -120,6 +261,12 @@ pub struct DnsRecordInfo {
     pub ttl: i64,
     #[serde(rename = "type")]
     pub record_type: String,
+    /// Priority of MX and SRV records
+    #[serde(default)]
+    pub priority: Option<u16>,
+    /// Structured data of e.g. SRV records
+    #[serde(default)]
+    pub data: Option<Value>,
 }
 
 impl std::fmt::Display for DnsRecordInfo {
@@ -157,6 +304,35 @@ pub struct DnsRecordModification {
     pub record_type: RecordType,
     pub content: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Request payload for patching an existing dns record. Fields that are `None` are left untouched.
+///
+/// See https://developers.cloudflare.com/api/operations/dns-records-for-a-zone-patch-dns-record.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnsRecordPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<RecordType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub ttl: Option<i64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub proxied: Option<bool>,
@@ -182,50 +358,389 @@ impl Zone {
         Zone::Name(name.to_string())
     }
 
-    pub async fn resolve(self, api: &CloudflareApi) -> Result<Option<Self>> {
-        self.lookup_id(api).await.map(|id| id.map(Zone::Identifier))
+    pub async fn resolve(self, provider: &(impl DnsProvider + ?Sized)) -> Result<Option<Self>> {
+        self.lookup_id(provider).await.map(|id| id.map(Zone::Identifier))
     }
 
-    pub async fn lookup_id(self, api: &CloudflareApi) -> Result<Option<String>> {
+    pub async fn lookup_id(self, provider: &(impl DnsProvider + ?Sized)) -> Result<Option<String>> {
         match self {
             Zone::Identifier(id) => Ok(Some(id)),
             Zone::Name(name) => {
                 debug!(?name, "looking up zone by name");
-                let accounts = api.list_zones().await?;
-                Ok(accounts.into_iter().find(|it| it.name == name).map(|it| it.id))
+                let zones = provider.list_zones().await?;
+                Ok(zones.into_iter().find(|it| it.name == name).map(|it| it.id))
             }
         }
     }
 }
 
-/// Arguments for [`create_dns_record`].
-#[derive(Debug)]
+/// Arguments for [`DnsProvider::create_dns_record`].
+#[derive(Debug, Clone)]
 pub struct CreateRecordArgs {
     pub zone: Zone,
     pub name: String,
     pub record_type: RecordType,
     pub content: String,
+    /// Priority of MX and SRV records
+    pub priority: Option<u16>,
+    /// Structured data of e.g. SRV records
+    pub data: Option<Value>,
     pub comment: Option<String>,
     pub ttl: Option<i64>,
+    pub proxied: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    /// UID of the resource owning the record. If set, the record is marked as owned by it, see [`ownership`].
+    pub owner: Option<String>,
+}
+
+impl CreateRecordArgs {
+    /// The comment to write, including the owner marker.
+    pub fn marked_comment(&self) -> Option<String> {
+        match self.owner.as_deref() {
+            Some(owner) => Some(ownership::with_marker(self.comment.as_deref(), owner)),
+            None => self.comment.clone(),
+        }
+    }
+}
+
+/// The type and content of a single desired record, as cloudflare represents it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordContent {
+    pub record_type: RecordType,
+    pub content: String,
+    /// Priority of MX and SRV records
+    pub priority: Option<u16>,
+    /// Structured data of e.g. SRV records
+    pub data: Option<Value>,
+}
+
+impl RecordContent {
+    /// A record with a plain content, such as an A record.
+    pub fn new(record_type: RecordType, content: impl ToString) -> Self {
+        Self {
+            record_type,
+            content: content.to_string(),
+            priority: None,
+            data: None,
+        }
+    }
+
+    /// Parses `content` in the zone file format of `record_type` (e.g. `10 mail.example.com` for MX). Contents of
+    /// types without structured data are taken as they are.
+    pub fn parse(record_type: RecordType, content: &str) -> Result<Self> {
+        match RecordData::parse(record_type, content)? {
+            Some(data) => Ok(Self::from_data(record_type, &data)),
+            None => Ok(Self::new(record_type, content)),
+        }
+    }
+
+    pub fn from_data(record_type: RecordType, data: &RecordData) -> Self {
+        Self {
+            record_type,
+            content: data.cloudflare_content(),
+            priority: data.cloudflare_priority(),
+            data: data.cloudflare_data(),
+        }
+    }
+
+    /// The content in zone file format, including the priority, as DNS lookups return it.
+    pub fn zone_format(&self) -> String {
+        match self.priority {
+            Some(priority) => format!("{priority} {}", self.content),
+            None => self.content.clone(),
+        }
+    }
+
+    fn matches(&self, record: &DnsRecordInfo) -> bool {
+        self.record_type.as_str() == record.record_type && self.content_matches(record) && self.priority_matches(record)
+    }
+
+    /// Compares the data fields if the record has structured data, otherwise the content.
+    fn content_matches(&self, record: &DnsRecordInfo) -> bool {
+        match (&self.data, &record.data) {
+            (Some(desired), Some(actual)) => {
+                let read = |data, priority| RecordData::from_cloudflare_data(self.record_type, data, priority);
+                match (read(desired, self.priority), read(actual, record.priority)) {
+                    (Some(desired), Some(actual)) => desired.equivalent(&actual),
+                    _ => data_value_matches(desired, actual),
+                }
+            }
+            _ => {
+                normalize_content(self.record_type, &self.content)
+                    == normalize_content(self.record_type, &record.content)
+            }
+        }
+    }
+
+    fn priority_matches(&self, record: &DnsRecordInfo) -> bool {
+        self.priority.is_none_or(|priority| record.priority == Some(priority))
+    }
+}
+
+/// Compares data that does not fit its record type field by field. Numbers are compared by value, cloudflare returns
+/// e.g. `46` for a `46.0`, everything else exactly. Fields cloudflare adds to the data are ignored.
+fn data_value_matches(desired: &Value, actual: &Value) -> bool {
+    match (desired, actual) {
+        (Value::Object(desired), Value::Object(actual)) => desired
+            .iter()
+            .all(|(key, value)| actual.get(key).is_some_and(|actual| data_value_matches(value, actual))),
+        (Value::Number(desired), Value::Number(actual)) => desired.as_f64() == actual.as_f64(),
+        _ => desired == actual,
+    }
+}
+
+/// Arguments for [`DnsProvider::sync_dns_records`]. Describes all records of one name.
+#[derive(Debug, Clone)]
+pub struct RecordSetArgs {
+    pub zone: Zone,
+    pub name: String,
+    /// One record is maintained per content.
+    pub contents: Vec<RecordContent>,
+    pub comment: Option<String>,
+    pub ttl: Option<i64>,
+    pub proxied: Option<bool>,
+    pub tags: Option<Vec<String>>,
+    /// UID of the resource owning the records. If set, the records are marked as owned by it, see [`ownership`].
+    pub owner: Option<String>,
+    pub ownership: OwnershipPolicy,
+}
+
+impl RecordSetArgs {
+    pub(super) fn create_args(&self, content: &RecordContent) -> CreateRecordArgs {
+        CreateRecordArgs {
+            zone: self.zone.clone(),
+            name: self.name.clone(),
+            record_type: content.record_type,
+            content: content.content.clone(),
+            priority: content.priority,
+            data: content.data.clone(),
+            comment: self.comment.clone(),
+            ttl: self.ttl,
+            proxied: self.proxied,
+            tags: self.tags.clone(),
+            owner: self.owner.clone(),
+        }
+    }
+
+    /// Whether the record may be modified or deleted on behalf of [`Self::owner`].
+    pub(super) fn may_manage(&self, record: &DnsRecordInfo) -> bool {
+        self.owner
+            .as_deref()
+            .is_none_or(|owner| Ownership::of(record, owner).is_manageable(self.ownership))
+    }
+
+    /// Whether a record that is no longer needed may be deleted. Records are only deleted if they were created by
+    /// [`Self::owner`], unless the ownership policy is [`OwnershipPolicy::Takeover`].
+    pub(super) fn may_delete(&self, record: &DnsRecordInfo) -> bool {
+        self.owner.is_none() || self.ownership == OwnershipPolicy::Takeover || self.owns(record)
+    }
+
+    /// Whether the record carries the owner marker of [`Self::owner`].
+    pub(super) fn owns(&self, record: &DnsRecordInfo) -> bool {
+        self.owner
+            .as_deref()
+            .is_some_and(|owner| Ownership::of(record, owner) == Ownership::Owned)
+    }
+
+    /// The comment to write, including the owner marker. A comment of an `existing` record is kept if none is
+    /// specified.
+    pub(super) fn marked_comment(&self, existing: Option<&DnsRecordInfo>) -> Option<String> {
+        let comment = self
+            .comment
+            .as_deref()
+            .or_else(|| existing.and_then(|record| ownership::strip_marker(record.comment.as_deref())));
+        match self.owner.as_deref() {
+            Some(owner) => Some(ownership::with_marker(comment, owner)),
+            None => comment.map(ToString::to_string),
+        }
+    }
+
+    /// Compares every managed attribute of `record` with the desired state for `content`. Attributes that are not
+    /// specified (such as a missing `ttl`) are left to cloudflare and not considered.
+    pub fn diff(&self, content: &RecordContent, record: &DnsRecordInfo) -> Vec<Drift> {
+        let mut drift = Vec::new();
+
+        if !content.content_matches(record) {
+            drift.push(Drift::new("content", &content.content, &record.content));
+        }
+        if !content.priority_matches(record) {
+            let actual = record.priority.map(|it| it.to_string()).unwrap_or_default();
+            drift.push(Drift::new("priority", content.priority.unwrap_or_default(), actual));
+        }
+        if let Some(ttl) = self.ttl.filter(|ttl| *ttl != record.ttl) {
+            drift.push(Drift::new("ttl", ttl, record.ttl));
+        }
+        if let Some(proxied) = self.proxied.filter(|proxied| *proxied != record.proxied) {
+            drift.push(Drift::new("proxied", proxied, record.proxied));
+        }
+        if let Some(comment) = self.comment.as_deref() {
+            let comment = match self.owner.as_deref() {
+                Some(owner) => ownership::truncate_comment(comment, owner),
+                None => comment,
+            };
+            let actual = ownership::strip_marker(record.comment.as_deref()).unwrap_or_default();
+            if actual != comment {
+                drift.push(Drift::new("comment", comment, actual));
+            }
+        }
+        if let Some(owner) = self.owner.as_deref() {
+            let actual = ownership::owner_of(record.comment.as_deref());
+            if actual != Some(owner) {
+                drift.push(Drift::new("owner", owner, actual.unwrap_or_default()));
+            }
+        }
+        if let Some(tags) = self.tags.as_ref() {
+            let mut desired = tags.clone();
+            let mut actual = record.tags.clone();
+            desired.sort();
+            actual.sort();
+            if desired != actual {
+                drift.push(Drift::new("tags", desired.join(","), actual.join(",")));
+            }
+        }
+
+        drift
+    }
+}
+
+/// A managed attribute of a dns record whose actual value differs from the desired one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Drift {
+    pub field: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Drift {
+    fn new(field: &'static str, expected: impl ToString, actual: impl ToString) -> Self {
+        Self {
+            field,
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {:?} (expected {:?})", self.field, self.actual, self.expected)
+    }
+}
+
+/// Result of [`DnsProvider::sync_dns_records`].
+#[derive(Debug, Clone)]
+pub struct SyncedRecords {
+    /// The records matching the desired contents, in the same order.
+    pub records: Vec<DnsRecordInfo>,
+    /// Attributes of already existing records that did not match and were patched. Empty if all records were newly
+    /// created or already up to date.
+    pub drift: Vec<Drift>,
+}
+
+/// Base URL of the cloudflare v4 API.
+pub const DEFAULT_API_URL: &str = "https://api.cloudflare.com/client/v4";
+
+/// Settings of the HTTP client used for cloudflare API requests.
+#[derive(Debug, Clone)]
+pub struct HttpOptions {
+    /// Proxy for all requests, e.g. `http://proxy.internal:3128`. If not set, the `HTTPS_PROXY` and `ALL_PROXY`
+    /// environment variables are honored.
+    pub proxy: Option<String>,
+    /// PEM file with CA certificates to trust in addition to the built-in roots, e.g. of a TLS intercepting proxy.
+    pub ca_certificates: Option<PathBuf>,
+    /// Timeout of a whole request, including reading the response.
+    pub timeout: StdDuration,
+    pub connect_timeout: StdDuration,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            proxy: None,
+            ca_certificates: None,
+            timeout: StdDuration::from_secs(30),
+            connect_timeout: StdDuration::from_secs(10),
+        }
+    }
+}
+
+impl HttpOptions {
+    /// Builds a client with these settings. Clients pool their connections, so one should be shared by all requests.
+    pub fn client(&self) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .connect_timeout(self.connect_timeout);
+        if let Some(proxy) = self.proxy.as_deref() {
+            let proxy = reqwest::Proxy::all(proxy).with_context(|| format!("invalid proxy {proxy:?}"))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(path) = self.ca_certificates.as_deref() {
+            let pem = std::fs::read(path).with_context(|| format!("unable to read CA certificates from {path:?}"))?;
+            let certificates = reqwest::Certificate::from_pem_bundle(&pem)
+                .with_context(|| format!("invalid CA certificates in {path:?}"))?;
+            for certificate in certificates {
+                builder = builder.add_root_certificate(certificate);
+            }
+        }
+        Ok(builder.build()?)
+    }
 }
 
 #[allow(clippy::type_complexity)]
 #[derive(Clone, Debug)]
 pub struct CloudflareApi {
     api_token: String,
+    /// Base URL of the API, without trailing slash. [`DEFAULT_API_URL`] unless overridden.
+    api_url: String,
+    /// Only zones of this account are listed, if set.
+    account_id: Option<String>,
+    http: reqwest::Client,
+    /// Shared by all clones and all clients using the same token, so that all reconciles stay within the rate limit
+    /// of the token together.
+    rate_limiter: Arc<RateLimiter>,
     list_zone_cache: Arc<Mutex<Option<(DateTime<Utc>, Vec<AccountInfo>)>>>,
     list_dns_records_cache: Arc<Mutex<HashMap<String, (DateTime<Utc>, Vec<DnsRecordInfo>)>>>,
 }
 
 impl CloudflareApi {
+    /// A client using [`DEFAULT_API_URL`] and the default [`HttpOptions`].
     pub fn new(api_token: String) -> Self {
         Self {
             api_token,
+            api_url: DEFAULT_API_URL.to_string(),
+            account_id: None,
+            http: HttpOptions::default().client().expect("default http client"),
+            rate_limiter: Default::default(),
             list_zone_cache: Default::default(),
             list_dns_records_cache: Default::default(),
         }
     }
 
+    /// Sends requests to `api_url` instead of [`DEFAULT_API_URL`], e.g. to a staging endpoint or a local fake of the
+    /// API in tests.
+    pub fn with_api_url(mut self, api_url: impl AsRef<str>) -> Self {
+        self.api_url = api_url.as_ref().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Only manages zones of the cloudflare account `account_id`, for tokens with access to several accounts.
+    pub fn with_account_id(mut self, account_id: impl ToString) -> Self {
+        self.account_id = Some(account_id.to_string());
+        self
+    }
+
+    /// Shares `rate_limiter` with other clients, which must use the same token as cloudflare limits requests per token.
+    pub fn with_rate_limiter(mut self, rate_limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Sends requests with `http`, see [`HttpOptions::client`].
+    pub fn with_http_client(mut self, http: reqwest::Client) -> Self {
+        self.http = http;
+        self
+    }
+
     async fn invalidate_dns_record_cache(&self, zone_identifier: impl AsRef<str>) {
         self.list_dns_records_cache
             .lock()
@@ -233,8 +748,9 @@ impl CloudflareApi {
             .remove(zone_identifier.as_ref());
     }
 
-    /// List all cloudflare accounts which represent zones.
-    pub async fn list_zones(&self) -> Result<Vec<AccountInfo>, eyre::Error> {
+    /// List all zones with everything cloudflare knows about them, see [`DnsProvider::list_zones`] for just their ids
+    /// and names.
+    pub async fn list_zone_infos(&self) -> Result<Vec<AccountInfo>, eyre::Error> {
         const CACHE_DURATION: Duration = Duration::minutes(5);
 
         let mut cache = self.list_zone_cache.lock().await;
@@ -244,18 +760,41 @@ impl CloudflareApi {
             }
         }
 
-        let url = "https://api.cloudflare.com/client/v4/zones";
-        let zones = cloudflare_api_request::<Vec<AccountInfo>, ()>(url, None, Method::GET, &self.api_token).await?;
+        let url = match &self.account_id {
+            Some(account_id) => format!("{}/zones?account.id={account_id}", self.api_url),
+            None => format!("{}/zones", self.api_url),
+        };
+        let zones = cloudflare_api_get_all::<AccountInfo, ()>(
+            &self.http,
+            &self.rate_limiter,
+            &url,
+            None,
+            Method::GET,
+            &self.api_token,
+        )
+        .await?;
         *cache = Some((Utc::now(), zones.clone()));
 
         Ok(zones)
     }
+}
+
+#[async_trait]
+impl DnsProvider for CloudflareApi {
+    async fn list_zones(&self) -> Result<Vec<ZoneInfo>> {
+        let zones = self.list_zone_infos().await?;
+        Ok(zones
+            .into_iter()
+            .map(|zone| ZoneInfo {
+                id: zone.id,
+                name: zone.name,
+            })
+            .collect())
+    }
 
-    /// List DNS records in a cloudflare zone.
-    pub async fn list_dns_records(&self, zone_identifier: impl AsRef<str>) -> Result<Vec<DnsRecordInfo>> {
+    async fn list_dns_records(&self, zone_identifier: &str) -> Result<Vec<DnsRecordInfo>> {
         const CACHE_DURATION: Duration = Duration::minutes(1);
 
-        let zone_identifier = zone_identifier.as_ref();
         let mut cache = self.list_dns_records_cache.lock().await;
 
         if let Some((time, records)) = cache.get(zone_identifier) {
@@ -264,8 +803,16 @@ impl CloudflareApi {
             }
         }
 
-        let url = format!("https://api.cloudflare.com/client/v4/zones/{zone_identifier}/dns_records");
-        let records = cloudflare_api_get_all::<DnsRecordInfo, ()>(&url, None, Method::GET, &self.api_token).await?;
+        let url = format!("{}/zones/{zone_identifier}/dns_records", self.api_url);
+        let records = cloudflare_api_get_all::<DnsRecordInfo, ()>(
+            &self.http,
+            &self.rate_limiter,
+            &url,
+            None,
+            Method::GET,
+            &self.api_token,
+        )
+        .await?;
         cache.insert(zone_identifier.to_string(), (Utc::now(), records.clone()));
 
         debug!(records = %records.len(), "listed dns records");
@@ -273,36 +820,44 @@ impl CloudflareApi {
         Ok(records)
     }
 
-    /// Create a new cloudflare dns record
-    pub async fn create_dns_record(&self, args: CreateRecordArgs) -> Result<DnsRecordInfo, eyre::Error> {
+    async fn create_dns_record(&self, args: CreateRecordArgs) -> Result<DnsRecordInfo> {
+        let comment = args.marked_comment();
         let CreateRecordArgs {
             zone,
             name,
             record_type,
             content,
-            comment,
+            priority,
+            data,
             ttl,
+            proxied,
+            tags,
+            ..
         } = args;
 
         let zone_identifier = zone
             .lookup_id(self)
             .await?
             .ok_or_else(|| eyre::eyre!("zone not found"))?;
-        let url = format!("https://api.cloudflare.com/client/v4/zones/{zone_identifier}/dns_records");
+        let url = format!("{}/zones/{zone_identifier}/dns_records", self.api_url);
         let id = util::id();
 
         info!(?id, ?name, r#type = ?record_type, "creating dns record");
         let result = cloudflare_api_request::<DnsRecordInfo, _>(
+            &self.http,
+            &self.rate_limiter,
             &url,
             Some(DnsRecordModification {
                 id,
                 name,
                 record_type,
                 content,
+                priority,
+                data,
                 ttl,
-                proxied: None,
+                proxied,
                 comment,
-                tags: None,
+                tags,
             }),
             Method::POST,
             &self.api_token,
@@ -311,97 +866,93 @@ impl CloudflareApi {
 
         self.invalidate_dns_record_cache(zone_identifier).await;
 
-        result
+        Ok(result?)
     }
 
-    /// Updates a cloudflare dns record... currently deletes and recreates... Will wait for the dns record to propagate,
-    /// i.e. a dns lookup resolves to the correct ip.
-    // TODO: we should use the proper patch api.
-    #[instrument(level = "debug", skip(self))]
-    pub async fn update_dns_record_and_wait(&self, args: CreateRecordArgs) -> Result<DnsRecordInfo, eyre::Error> {
-        let Some(zone_id) = args.zone.clone().lookup_id(self).await? else {
-            bail!("zone not found");
-        };
-
-        debug!("updating dns record");
-
-        let domain = args.name.clone();
-        let dns_records = self.list_dns_records(&zone_id).await?;
-
-        let mut existing = None;
-        for record in dns_records.into_iter().filter(|record| record.name == domain) {
-            if record.content == args.content {
-                existing = Some(record);
-                info!("DNS record for {domain:?} already exists with {:?}", args.content);
-                continue;
-            }
+    async fn update_dns_record(&self, zone_identifier: &str, id: &str, patch: DnsRecordPatch) -> Result<DnsRecordInfo> {
+        let url = format!("{}/zones/{zone_identifier}/dns_records/{id}", self.api_url);
 
-            warn!(
-                "Found existing DNS record for web domain {domain:?} with ip {:?}. Deleting.",
-                record.content
-            );
-            self.delete_dns_record(&zone_id, &record.id)
-                .await
-                .context("Failed to delete existing DNS record")?;
-        }
-
-        if let Some(existing) = existing {
-            return Ok(existing);
-        }
-
-        info!("Creating new DNS record for {domain:?} with {:?}", args.content);
-        let record = self.create_dns_record(args).await?;
-        debug!("Registered record for {domain:?} with {:?}", record.content);
+        info!(?id, content = ?patch.content, "updating dns record");
+        let result = cloudflare_api_request::<DnsRecordInfo, _>(
+            &self.http,
+            &self.rate_limiter,
+            &url,
+            Some(patch),
+            Method::PATCH,
+            &self.api_token,
+        )
+        .await;
 
-        self.invalidate_dns_record_cache(zone_id).await;
+        self.invalidate_dns_record_cache(zone_identifier).await;
 
-        Ok(record)
+        Ok(result?)
     }
 
-    /// Delete a DNS record by its (domain) name using the cloudflare API
-    #[allow(dead_code)]
-    pub async fn delete_dns_records_by_name(
-        &self,
-        name: impl AsRef<str>,
-        zone_identifier: impl AsRef<str>,
-    ) -> Result<(), eyre::Error> {
-        let name = name.as_ref();
-        let zone_identifier = zone_identifier.as_ref();
+    async fn delete_dns_record(&self, zone_identifier: &str, id: &str) -> Result<()> {
+        let url = format!("{}/zones/{zone_identifier}/dns_records/{id}", self.api_url);
 
-        info!(?name, "deleting dns record by name");
-        let records = self
-            .list_dns_records(&zone_identifier)
-            .await?
-            .into_iter()
-            .filter(|it| it.name == name);
+        cloudflare_api_request::<Value, ()>(
+            &self.http,
+            &self.rate_limiter,
+            &url,
+            None,
+            Method::DELETE,
+            &self.api_token,
+        )
+        .await?;
 
-        for record in records {
-            self.delete_dns_record(zone_identifier, record.id).await?;
-        }
+        self.invalidate_dns_record_cache(zone_identifier).await;
 
         Ok(())
     }
+}
 
-    /// Delete a DNS record by its id using the cloudflare API.
-    pub async fn delete_dns_record(&self, zone_identifier: impl AsRef<str>, id: impl AsRef<str>) -> Result<()> {
-        let zone_identifier = zone_identifier.as_ref();
-        let id = id.as_ref();
-        let url = format!("https://api.cloudflare.com/client/v4/zones/{zone_identifier}/dns_records/{id}");
-
-        cloudflare_api_request::<Value, ()>(&url, None, Method::DELETE, &self.api_token).await?;
+/// Assignment of existing records to desired contents, see [`plan_record_set`].
+#[derive(Debug)]
+pub(super) struct RecordSetPlan {
+    /// For each desired content, the existing record to keep or patch. `None` if a new record needs to be created.
+    pub existing: Vec<Option<DnsRecordInfo>>,
+    /// Existing records that are not needed anymore.
+    pub superfluous: Vec<DnsRecordInfo>,
+}
 
-        self.invalidate_dns_record_cache(zone_identifier).await;
+/// Assigns existing records to desired contents. Records that already have a desired content are kept, remaining
+/// records of the same type are reused for remaining contents so they can be patched in place.
+pub(super) fn plan_record_set(contents: &[RecordContent], mut records: Vec<DnsRecordInfo>) -> RecordSetPlan {
+    let mut existing = contents
+        .iter()
+        .map(|content| {
+            let index = records.iter().position(|record| content.matches(record))?;
+            Some(records.remove(index))
+        })
+        .collect::<Vec<_>>();
+
+    for (content, slot) in contents.iter().zip(existing.iter_mut()) {
+        if slot.is_some() {
+            continue;
+        }
+        if let Some(index) = records
+            .iter()
+            .position(|record| record.record_type == content.record_type.as_str())
+        {
+            *slot = Some(records.remove(index));
+        }
+    }
 
-        Ok(())
+    RecordSetPlan {
+        existing,
+        superfluous: records,
     }
 }
 
 pub async fn cloudflare_api_get_all<T, B>(
+    client: &reqwest::Client,
+    rate_limiter: &RateLimiter,
     url: &str,
     body: Option<B>,
     method: Method,
     api_token: impl AsRef<str>,
-) -> Result<Vec<T>>
+) -> Result<Vec<T>, CloudflareError>
 where
     B: Serialize + Clone,
     T: DeserializeOwned,
@@ -418,7 +969,15 @@ where
             format!("{url}?{query}")
         };
 
-        let res = cloudflare_api_request_inner::<Vec<T>, _>(&url, body.clone(), method.clone(), api_token).await?;
+        let res = cloudflare_api_request_inner::<Vec<T>, _>(
+            client,
+            rate_limiter,
+            &url,
+            body.clone(),
+            method.clone(),
+            api_token,
+        )
+        .await?;
 
         let page_len = res.result.len();
         results.extend(res.result);
@@ -445,9 +1004,144 @@ fn has_more_pages(info: Option<&ApiResultInfo>, page_len: usize, current_page: u
     page_len > 0 && current_page < info.total_pages
 }
 
+pub async fn cloudflare_api_request<R, B>(
+    client: &reqwest::Client,
+    rate_limiter: &RateLimiter,
+    url: &str,
+    body: Option<B>,
+    method: Method,
+    api_token: impl AsRef<str>,
+) -> Result<R, CloudflareError>
+where
+    B: Serialize,
+    R: DeserializeOwned,
+{
+    let body = cloudflare_api_request_inner(client, rate_limiter, url, body, method, api_token).await?;
+    Ok(body.result)
+}
+
+/// Retries of a request after 429 Too Many Requests and, for idempotent requests, server or connection errors.
+const MAX_RETRIES: u32 = 4;
+
+/// The delay the `Retry-After` header of a response asks for, in seconds.
+fn retry_after(res: &reqwest::Response) -> Option<StdDuration> {
+    let seconds = res
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(StdDuration::from_secs(seconds))
+}
+
+async fn cloudflare_api_request_inner<R, B>(
+    client: &reqwest::Client,
+    rate_limiter: &RateLimiter,
+    url: &str,
+    body: Option<B>,
+    method: Method,
+    api_token: impl AsRef<str>,
+) -> Result<ApiResult<R>, CloudflareError>
+where
+    B: Serialize,
+    R: DeserializeOwned,
+{
+    let body = body
+        .map(|body| serde_json::to_vec(&body))
+        .transpose()
+        .map_err(CloudflareError::InvalidRequest)?;
+    // Requests that were rejected because of the rate limit were not processed and can always be retried, others only
+    // if repeating them cannot do any harm.
+    let idempotent = matches!(method, Method::GET | Method::PUT | Method::DELETE);
+
+    let mut attempt = 0;
+    let res = loop {
+        rate_limiter.acquire().await;
+        debug!("[{method}] {url} cloudflare API request");
+
+        let req = client
+            .request(method.clone(), url)
+            .bearer_auth(api_token.as_ref())
+            .header("Content-Type", "application/json");
+        let req = if let Some(body) = body.clone() {
+            req.body(body)
+        } else {
+            req
+        };
+        let result = req.send().await;
+
+        let retry = match &result {
+            Ok(res) if res.status() == StatusCode::TOO_MANY_REQUESTS => {
+                let delay = retry_after(res).unwrap_or_else(|| rate_limit::backoff(attempt));
+                rate_limiter.pause(delay);
+                Some((format!("rate limited, retry after {delay:?}"), StdDuration::ZERO))
+            }
+            Ok(res) if idempotent && res.status().is_server_error() => {
+                Some((format!("status {}", res.status()), rate_limit::backoff(attempt)))
+            }
+            Err(err) if idempotent && (err.is_timeout() || err.is_connect()) => {
+                Some((err.to_string(), rate_limit::backoff(attempt)))
+            }
+            _ => None,
+        };
+        match retry {
+            Some((reason, delay)) if attempt < MAX_RETRIES => {
+                warn!("[{method}] {url} cloudflare API request failed ({reason}), retrying");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            _ => break result?,
+        }
+    };
+
+    let status = res.status();
+    if !status.is_success() {
+        let retry_after = retry_after(&res);
+        let text = res.text().await?;
+        let errors = match serde_json::from_str::<ApiErrorResult>(&text) {
+            Ok(body) => body.errors,
+            Err(_) => vec![ApiError { code: 0, message: text }],
+        };
+        return Err(CloudflareError::from_response(status, errors, retry_after));
+    }
+
+    #[cfg(debug_assertions)]
+    let body: ApiResult<R> = {
+        let body: Value = res
+            .json()
+            .await
+            .map_err(|err| CloudflareError::InvalidResponse(err.to_string()))?;
+        match serde_json::from_value(body.clone()) {
+            Err(err) => {
+                return Err(CloudflareError::InvalidResponse(format!(
+                    "{err}: {}",
+                    serde_json::to_string_pretty(&body).expect("pretty json")
+                )))
+            }
+            Ok(it) => it,
+        }
+    };
+
+    #[cfg(not(debug_assertions))]
+    let body: ApiResult<R> = res
+        .json()
+        .await
+        .map_err(|err| CloudflareError::InvalidResponse(err.to_string()))?;
+
+    if !body.success {
+        return Err(CloudflareError::from_response(status, body.errors, None));
+    }
+
+    Ok(body)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::dns::fake_api::FakeCloudflareApi;
+    use serde_json::json;
 
     fn info(total_pages: usize) -> ApiResultInfo {
         ApiResultInfo {
@@ -459,6 +1153,213 @@ mod tests {
         }
     }
 
+    fn record(content: &str) -> DnsRecordInfo {
+        DnsRecordInfo {
+            comment: Some("managed".to_string()),
+            content: content.to_string(),
+            created_on: Utc::now(),
+            id: "abc".to_string(),
+            meta: DnsRecordMeta {
+                auto_added: None,
+                managed_by_apps: None,
+                managed_by_argo_tunnel: None,
+            },
+            modified_on: Utc::now(),
+            name: "foo.example.com".to_string(),
+            proxiable: true,
+            proxied: false,
+            tags: vec!["b:2".to_string(), "a:1".to_string()],
+            ttl: 300,
+            record_type: "A".to_string(),
+            priority: None,
+            data: None,
+        }
+    }
+
+    fn a(content: &str) -> RecordContent {
+        RecordContent::new(RecordType::A, content)
+    }
+
+    fn args(content: &str) -> RecordSetArgs {
+        RecordSetArgs {
+            zone: Zone::id("zone"),
+            name: "foo.example.com".to_string(),
+            contents: vec![a(content)],
+            comment: Some("managed".to_string()),
+            ttl: Some(300),
+            proxied: Some(false),
+            tags: Some(vec!["a:1".to_string(), "b:2".to_string()]),
+            owner: None,
+            ownership: OwnershipPolicy::Adopt,
+        }
+    }
+
+    #[test]
+    fn diff_of_matching_record_is_empty() {
+        assert!(args("1.2.3.4").diff(&a("1.2.3.4"), &record("1.2.3.4")).is_empty());
+
+        let unmanaged = RecordSetArgs {
+            comment: None,
+            ttl: None,
+            proxied: None,
+            tags: None,
+            ..args("1.2.3.4")
+        };
+        let mut other = record("1.2.3.4");
+        other.proxied = true;
+        other.ttl = 1;
+        other.tags = Vec::new();
+        assert!(unmanaged.diff(&a("1.2.3.4"), &other).is_empty());
+    }
+
+    #[test]
+    fn diff_reports_every_drifted_attribute() {
+        let mut actual = record("1.2.3.5");
+        actual.ttl = 1;
+        actual.proxied = true;
+        actual.comment = None;
+        actual.tags = vec!["a:1".to_string()];
+
+        let fields = args("1.2.3.4")
+            .diff(&a("1.2.3.4"), &actual)
+            .into_iter()
+            .map(|drift| drift.field)
+            .collect::<Vec<_>>();
+        assert_eq!(fields, ["content", "ttl", "proxied", "comment", "tags"]);
+    }
+
+    #[test]
+    fn diff_ignores_owner_marker_in_comment() {
+        let owned = RecordSetArgs {
+            owner: Some("uid-1".to_string()),
+            ..args("1.2.3.4")
+        };
+
+        let mut actual = record("1.2.3.4");
+        let drift = owned.diff(&a("1.2.3.4"), &actual);
+        assert_eq!(drift, [Drift::new("owner", "uid-1", "")]);
+
+        actual.comment = owned.marked_comment(Some(&actual));
+        assert_eq!(
+            actual.comment.as_deref(),
+            Some("managed [cloudflare-dns-operator:uid-1]")
+        );
+        assert!(owned.diff(&a("1.2.3.4"), &actual).is_empty());
+    }
+
+    #[test]
+    fn equivalent_contents_match() {
+        let mut actual = record("2001:db8::1");
+        actual.record_type = "AAAA".to_string();
+        assert!(RecordContent::new(RecordType::AAAA, "2001:DB8:0::1").matches(&actual));
+
+        let mut actual = record("\"first chunk\" \"second chunk\"");
+        actual.record_type = "TXT".to_string();
+        assert!(RecordContent::new(RecordType::TXT, "first chunksecond chunk").matches(&actual));
+        assert!(!RecordContent::new(RecordType::TXT, "first chunk").matches(&actual));
+    }
+
+    #[test]
+    fn structured_records_compare_priority_and_data() {
+        let mx = RecordContent::parse(RecordType::MX, "10 mail.example.com.").unwrap();
+        let mut actual = record("mail.example.com");
+        actual.record_type = "MX".to_string();
+        actual.priority = Some(10);
+        assert!(mx.matches(&actual));
+        assert!(args("").diff(&mx, &actual).is_empty());
+
+        actual.priority = Some(20);
+        assert!(!mx.matches(&actual));
+        assert_eq!(args("").diff(&mx, &actual), [Drift::new("priority", 10, 20)]);
+
+        let srv = RecordContent::parse(RecordType::SRV, "10 5 5060 sip.example.com").unwrap();
+        assert_eq!(srv.zone_format(), "10 5 5060 sip.example.com");
+        let mut actual = record("5 5060 sip.example.com");
+        actual.record_type = "SRV".to_string();
+        actual.priority = Some(10);
+        actual.data = Some(json!({"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.com"}));
+        assert!(srv.matches(&actual));
+
+        actual.data = Some(json!({"priority": 10, "weight": 5, "port": 5061, "target": "sip.example.com"}));
+        assert!(!srv.matches(&actual));
+        assert_eq!(args("").diff(&srv, &actual)[0].field, "content");
+
+        let loc = RecordContent::parse(RecordType::LOC, "52 31 12 N 13 24 36 E 34m").unwrap();
+        let mut actual = record("52 31 12.000 N 13 24 36.000 E 34.00m 1.00m 10000.00m 10.00m");
+        actual.record_type = "LOC".to_string();
+        actual.data = Some(json!({
+            "lat_degrees": 52, "lat_minutes": 31, "lat_seconds": 12, "lat_direction": "N",
+            "long_degrees": 13, "long_minutes": 24, "long_seconds": 36, "long_direction": "E",
+            "altitude": 34, "size": 1, "precision_horz": 10000, "precision_vert": 10,
+        }));
+        assert!(loc.matches(&actual));
+    }
+
+    #[test]
+    fn only_case_insensitive_data_fields_ignore_case() {
+        let srv = RecordContent::parse(RecordType::SRV, "10 5 5060 sip.example.com").unwrap();
+        let mut actual = record("5 5060 SIP.Example.com.");
+        actual.record_type = "SRV".to_string();
+        actual.priority = Some(10);
+        actual.data = Some(json!({"priority": 10, "weight": 5, "port": 5060, "target": "SIP.Example.com."}));
+        assert!(srv.matches(&actual));
+
+        for (record_type, desired, data) in [
+            (
+                RecordType::CAA,
+                "0 issue \"letsencrypt.org\"",
+                json!({"flags": 0, "tag": "ISSUE", "value": "LetsEncrypt.org"}),
+            ),
+            (
+                RecordType::URI,
+                "10 1 \"https://example.com/path\"",
+                json!({"weight": 1, "target": "https://example.com/PATH"}),
+            ),
+            (
+                RecordType::CERT,
+                "1 0 0 TUlJQg==",
+                json!({"type": 1, "key_tag": 0, "algorithm": 0, "certificate": "tuljqg=="}),
+            ),
+        ] {
+            let desired = RecordContent::parse(record_type, desired).unwrap();
+            let mut actual = record("");
+            actual.record_type = record_type.as_str().to_string();
+            actual.priority = desired.priority;
+            actual.data = Some(data);
+            assert!(!desired.matches(&actual), "{record_type:?}");
+        }
+    }
+
+    #[test]
+    fn record_set_plan_keeps_matching_records_and_reuses_others() {
+        let contents = ["1.1.1.1", "2.2.2.2", "3.3.3.3"].map(a);
+        let mut existing = ["4.4.4.4", "2.2.2.2", "5.5.5.5", "6.6.6.6", "::1"].map(record).to_vec();
+        existing[4].record_type = "AAAA".to_string();
+
+        let plan = plan_record_set(&contents, existing);
+        let existing = plan
+            .existing
+            .iter()
+            .map(|record| record.as_ref().map(|record| record.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(existing, [Some("4.4.4.4"), Some("2.2.2.2"), Some("5.5.5.5")]);
+        let superfluous = plan
+            .superfluous
+            .iter()
+            .map(|record| record.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(superfluous, ["6.6.6.6", "::1"]);
+
+        let plan = plan_record_set(&contents, vec![record("3.3.3.3")]);
+        let existing = plan
+            .existing
+            .iter()
+            .map(|record| record.as_ref().map(|record| record.content.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(existing, [None, None, Some("3.3.3.3")]);
+        assert!(plan.superfluous.is_empty());
+    }
+
     #[test]
     fn pagination_stops_on_last_page() {
         assert!(has_more_pages(Some(&info(2)), 100, 1));
@@ -479,65 +1380,233 @@ mod tests {
         assert!(!has_more_pages(None, 100, 1));
         assert!(!has_more_pages(Some(&info(5)), 0, 2));
     }
-}
 
-pub async fn cloudflare_api_request<R, B>(
-    url: &str,
-    body: Option<B>,
-    method: Method,
-    api_token: impl AsRef<str>,
-) -> Result<R>
-where
-    B: Serialize,
-    R: DeserializeOwned,
-{
-    let body = cloudflare_api_request_inner(url, body, method, api_token).await?;
-    Ok(body.result)
-}
+    #[test]
+    fn http_options_build_clients() {
+        assert!(HttpOptions::default().client().is_ok());
+        let options = HttpOptions {
+            proxy: Some("http://proxy.internal:3128".to_string()),
+            ..Default::default()
+        };
+        assert!(options.client().is_ok());
 
-async fn cloudflare_api_request_inner<R, B>(
-    url: &str,
-    body: Option<B>,
-    method: Method,
-    api_token: impl AsRef<str>,
-) -> Result<ApiResult<R>>
-where
-    B: Serialize,
-    R: DeserializeOwned,
-{
-    debug!("[{method}] {url} cloudflare API request");
+        let options = HttpOptions {
+            proxy: Some("not a proxy".to_string()),
+            ..Default::default()
+        };
+        assert!(options.client().is_err());
+        let options = HttpOptions {
+            ca_certificates: Some("/does/not/exist.pem".into()),
+            ..Default::default()
+        };
+        assert!(options.client().is_err());
+    }
 
-    let req = reqwest::Client::new()
-        .request(method, url)
-        .bearer_auth(api_token.as_ref())
-        .header("Content-Type", "application/json");
+    #[tokio::test]
+    async fn zones_of_the_account_are_listed_from_all_pages() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.set_per_page(2);
+        for i in 0..5 {
+            fake.add_account_zone("other", &format!("other{i}"), &format!("other{i}.com"));
+        }
+        fake.add_account_zone("account2", "zone1", "example.com");
+        fake.add_account_zone("account2", "zone2", "example.org");
+        fake.add_account_zone("account2", "zone3", "example.net");
+        let api = fake.client().with_account_id("account2");
+
+        let zones = api.list_zones().await.unwrap();
+        assert_eq!(
+            zones.iter().map(|zone| zone.id.as_str()).collect::<Vec<_>>(),
+            ["zone1", "zone2", "zone3"]
+        );
+        assert_eq!(
+            fake.requests(),
+            [
+                "GET /zones?account.id=account2&page=1",
+                "GET /zones?account.id=account2&page=2",
+            ]
+        );
+    }
 
-    let req = if let Some(body) = body { req.json(&body) } else { req };
+    #[tokio::test]
+    async fn client_pages_and_caches_dns_records() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        fake.set_per_page(2);
+        for i in 0..5 {
+            fake.add_record("zone1", &format!("host{i}.example.com"), "A", "1.2.3.4");
+        }
+        let api = fake.client();
+
+        let records = api.list_dns_records("zone1").await.unwrap();
+        assert_eq!(records.len(), 5);
+        assert_eq!(
+            fake.requests(),
+            [
+                "GET /zones/zone1/dns_records?page=1",
+                "GET /zones/zone1/dns_records?page=2",
+                "GET /zones/zone1/dns_records?page=3",
+            ]
+        );
 
-    let res = req.send().await?;
+        // Served from the cache until a modification invalidates it.
+        api.list_dns_records("zone1").await.unwrap();
+        assert_eq!(fake.requests().len(), 3);
+
+        let created = api
+            .create_dns_record(
+                RecordSetArgs {
+                    zone: Zone::id("zone1"),
+                    owner: Some("uid-1".to_string()),
+                    ..args("5.6.7.8")
+                }
+                .create_args(&a("5.6.7.8")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(created.content, "5.6.7.8");
+        assert_eq!(ownership::owner_of(created.comment.as_deref()), Some("uid-1"));
+        assert_eq!(api.list_dns_records("zone1").await.unwrap().len(), 6);
+    }
 
-    if !res.status().is_success() {
-        bail!(
-            "cloudflare api error: status={:?}, body={:?}",
-            res.status(),
-            res.text().await?
+    #[tokio::test]
+    async fn client_reports_api_errors() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+
+        let cloudflare_error = |err: eyre::Report| err.downcast::<CloudflareError>().unwrap();
+
+        let unauthorized = CloudflareApi::new("wrong".to_string()).with_api_url(fake.url());
+        let err = cloudflare_error(unauthorized.list_zones().await.unwrap_err());
+        assert!(
+            matches!(err, CloudflareError::Authentication { status, .. } if status == StatusCode::FORBIDDEN),
+            "{err:?}"
+        );
+        assert_eq!(err.codes(), [10000]);
+
+        let api = fake.client();
+        let err = cloudflare_error(api.list_dns_records("unknown").await.unwrap_err());
+        assert!(matches!(err, CloudflareError::NotFound { .. }), "{err:?}");
+        let err = cloudflare_error(api.delete_dns_record("zone1", "unknown").await.unwrap_err());
+        assert_eq!(err.codes(), [81044]);
+
+        let mut cname = args("1.2.3.4").create_args(&RecordContent::new(RecordType::CNAME, "bar.example.com"));
+        cname.zone = Zone::id("zone1");
+        api.create_dns_record(cname.clone()).await.unwrap();
+        let err = cloudflare_error(api.create_dns_record(cname).await.unwrap_err());
+        assert!(matches!(err, CloudflareError::Conflict { .. }), "{err:?}");
+        assert_eq!(err.reason(), "conflicting record");
+        assert!(
+            err.to_string().contains("CNAME record with that host already exists"),
+            "{err}"
         );
+
+        let invalid = CreateRecordArgs {
+            zone: Zone::id("zone1"),
+            content: String::new(),
+            ..args("1.2.3.4").create_args(&a("1.2.3.4"))
+        };
+        let err = cloudflare_error(api.create_dns_record(invalid).await.unwrap_err());
+        assert!(matches!(err, CloudflareError::Validation { .. }), "{err:?}");
+        assert_eq!(err.codes(), [9005]);
     }
 
-    #[cfg(debug_assertions)]
-    let body: ApiResult<R> = {
-        let body: Value = res.json().await?;
-        match serde_json::from_value(body.clone()) {
-            Err(err) => bail!(
-                "failed to parse api response: {err:?}: {}",
-                serde_json::to_string_pretty(&body).expect("pretty json")
-            ),
-            Ok(it) => it,
-        }
-    };
+    #[tokio::test]
+    async fn client_retries_rate_limited_and_failed_requests() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        let api = fake.client();
 
-    #[cfg(not(debug_assertions))]
-    let body: ApiResult<_> = res.json().await?;
+        fake.rate_limit(1);
+        assert_eq!(api.list_zones().await.unwrap()[0].name, "example.com");
+        assert_eq!(fake.requests(), ["GET /zones?page=1", "GET /zones?page=1"]);
 
-    Ok(body)
+        fake.unavailable(2);
+        assert!(api.list_dns_records("zone1").await.unwrap().is_empty());
+        assert_eq!(fake.requests().len(), 5);
+
+        // Creating a record is not idempotent and only retried when rate limited.
+        fake.unavailable(1);
+        let err = api
+            .create_dns_record(args("1.2.3.4").create_args(&a("1.2.3.4")))
+            .await
+            .unwrap_err();
+        assert!(format!("{err:#}").contains("503"), "{err:#}");
+        assert_eq!(fake.requests().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn sync_dns_records_through_the_api() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        let unowned = fake.add_record("zone1", "foo.example.com", "A", "9.9.9.9");
+        let api = fake.client();
+
+        let mut args = RecordSetArgs {
+            zone: Zone::name("example.com"),
+            owner: Some("uid-1".to_string()),
+            contents: vec![a("1.2.3.4"), a("5.6.7.8")],
+            ..args("1.2.3.4")
+        };
+        let synced = api.sync_dns_records(args.clone()).await.unwrap();
+        assert_eq!(synced.records.len(), 2);
+        // The unowned record was adopted and patched in place.
+        assert_eq!(synced.records[0].id, unowned);
+
+        let records = fake.records("zone1");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["content"], "1.2.3.4");
+        assert_eq!(records[1]["content"], "5.6.7.8");
+        assert!(records.iter().all(|record| record["ttl"] == 300));
+
+        args.contents = vec![RecordContent::new(RecordType::CNAME, "bar.example.com")];
+        let synced = api.sync_dns_records(args).await.unwrap();
+        assert_eq!(synced.records.len(), 1);
+        let records = fake.records("zone1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["type"], "CNAME");
+    }
+
+    #[tokio::test]
+    async fn changed_records_are_patched_in_place() {
+        let fake = FakeCloudflareApi::start().await;
+        fake.add_zone("zone1", "example.com");
+        let api = fake.client();
+
+        let args = RecordSetArgs {
+            zone: Zone::name("example.com"),
+            owner: Some("uid-1".to_string()),
+            ..args("1.2.3.4")
+        };
+        let created = api.sync_dns_records(args.clone()).await.unwrap().records[0].id.clone();
+        let sent = fake.requests().len();
+
+        let synced = api
+            .sync_dns_records(RecordSetArgs {
+                contents: vec![a("5.6.7.8")],
+                ttl: Some(600),
+                ..args
+            })
+            .await
+            .unwrap();
+        assert_eq!(synced.records[0].id, created);
+
+        let requests = fake.requests().split_off(sent);
+        assert!(
+            requests
+                .iter()
+                .any(|request| request.starts_with(&format!("PATCH /zones/zone1/dns_records/{created}"))),
+            "{requests:?}"
+        );
+        assert!(
+            !requests
+                .iter()
+                .any(|request| request.starts_with("POST") || request.starts_with("DELETE")),
+            "{requests:?}"
+        );
+        let records = fake.records("zone1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["content"], "5.6.7.8");
+        assert_eq!(records[0]["ttl"], 600);
+    }
 }
@@ -0,0 +1,420 @@
+//! A fake of the cloudflare API for tests. Serves zones and dns records from memory over HTTP, paginates listings like
+//! cloudflare does and answers failures with cloudflare's error envelopes. Rate limiting and outages can be simulated
+//! with [`FakeCloudflareApi::rate_limit`] and [`FakeCloudflareApi::unavailable`].
+
+use super::cloudflare::CloudflareApi;
+use chrono::Utc;
+use http_body_util::{
+    BodyExt as _,
+    Full,
+};
+use hyper::{
+    body::{
+        Bytes,
+        Incoming,
+    },
+    header,
+    server::conn::http1,
+    service::service_fn,
+    Method,
+    Request,
+    Response,
+    StatusCode,
+};
+use hyper_util::rt::TokioIo;
+use serde_json::{
+    json,
+    Value,
+};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+use tokio::net::TcpListener;
+
+/// Path prefix of all endpoints, like the real API.
+const PREFIX: &str = "/client/v4";
+
+/// A fake cloudflare API listening on a random local port until the test runtime shuts down.
+#[derive(Clone)]
+pub struct FakeCloudflareApi {
+    url: String,
+    state: Arc<Mutex<State>>,
+}
+
+struct State {
+    per_page: usize,
+    zones: Vec<Value>,
+    records: HashMap<String, Vec<Value>>,
+    /// Number of upcoming requests answered with 429.
+    rate_limited: usize,
+    /// Number of upcoming requests answered with 503.
+    unavailable: usize,
+    /// `{method} {path}?{query}` of every request received.
+    requests: Vec<String>,
+    next_id: usize,
+}
+
+/// A response of the fake.
+struct Reply {
+    status: StatusCode,
+    retry_after: Option<u64>,
+    body: Value,
+}
+
+impl Reply {
+    fn ok(result: Value) -> Self {
+        Self {
+            status: StatusCode::OK,
+            retry_after: None,
+            body: json!({ "success": true, "errors": [], "messages": [], "result": result }),
+        }
+    }
+
+    fn error(status: StatusCode, code: u32, message: &str) -> Self {
+        Self {
+            status,
+            retry_after: None,
+            body: json!({
+                "success": false,
+                "errors": [{ "code": code, "message": message }],
+                "messages": [],
+                "result": null,
+            }),
+        }
+    }
+}
+
+impl FakeCloudflareApi {
+    /// The token clients have to authenticate with.
+    pub const TOKEN: &str = "fake-token";
+
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("bind fake cloudflare api");
+        let url = format!("http://{}{PREFIX}", listener.local_addr().expect("local addr"));
+        let state = Arc::new(Mutex::new(State {
+            per_page: 100,
+            zones: Vec::new(),
+            records: HashMap::new(),
+            rate_limited: 0,
+            unavailable: 0,
+            requests: Vec::new(),
+            next_id: 1,
+        }));
+
+        tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    let state = state.clone();
+                    tokio::spawn(async move {
+                        let service = service_fn(move |req| handle(state.clone(), req));
+                        let _ = http1::Builder::new()
+                            .serve_connection(TokioIo::new(stream), service)
+                            .await;
+                    });
+                }
+            }
+        });
+
+        Self { url, state }
+    }
+
+    /// Base URL of the fake, to be used with [`CloudflareApi::with_api_url`].
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A client talking to the fake.
+    pub fn client(&self) -> CloudflareApi {
+        CloudflareApi::new(Self::TOKEN.to_string()).with_api_url(&self.url)
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("fake api state")
+    }
+
+    pub fn add_zone(&self, id: &str, name: &str) {
+        self.add_account_zone("account1", id, name);
+    }
+
+    /// Adds a zone of the cloudflare account `account_id`.
+    pub fn add_account_zone(&self, account_id: &str, id: &str, name: &str) {
+        let mut zone = zone(id, name);
+        zone["account"]["id"] = json!(account_id);
+        self.state().zones.push(zone);
+    }
+
+    /// Adds a record as if it was created outside of the operator. Returns its id.
+    pub fn add_record(&self, zone_id: &str, name: &str, record_type: &str, content: &str) -> String {
+        let mut state = self.state();
+        let record = state.new_record(json!({ "name": name, "type": record_type, "content": content }));
+        let id = record["id"].as_str().expect("record id").to_string();
+        state.records.entry(zone_id.to_string()).or_default().push(record);
+        id
+    }
+
+    pub fn records(&self, zone_id: &str) -> Vec<Value> {
+        self.state().records.get(zone_id).cloned().unwrap_or_default()
+    }
+
+    /// Number of results per page of listings.
+    pub fn set_per_page(&self, per_page: usize) {
+        self.state().per_page = per_page;
+    }
+
+    /// Answers the next `requests` requests with 429 Too Many Requests.
+    pub fn rate_limit(&self, requests: usize) {
+        self.state().rate_limited = requests;
+    }
+
+    /// Answers the next `requests` requests with 503 Service Unavailable.
+    pub fn unavailable(&self, requests: usize) {
+        self.state().unavailable = requests;
+    }
+
+    /// `{method} {path}?{query}` of all requests received so far, without the API prefix.
+    pub fn requests(&self) -> Vec<String> {
+        self.state().requests.clone()
+    }
+}
+
+async fn handle(state: Arc<Mutex<State>>, req: Request<Incoming>) -> Result<Response<Full<Bytes>>, Infallible> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    let query = req.uri().query().unwrap_or_default().to_string();
+    let authorized = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|it| it.as_bytes() == format!("Bearer {}", FakeCloudflareApi::TOKEN).as_bytes());
+    let body = match req.into_body().collect().await {
+        Ok(body) => serde_json::from_slice::<Value>(&body.to_bytes()).ok(),
+        Err(_) => None,
+    };
+
+    let reply = state
+        .lock()
+        .expect("fake api state")
+        .handle(&method, &path, &query, authorized, body);
+
+    let mut response = Response::builder()
+        .status(reply.status)
+        .header(header::CONTENT_TYPE, "application/json");
+    if let Some(retry_after) = reply.retry_after {
+        response = response.header(header::RETRY_AFTER, retry_after);
+    }
+    Ok(response
+        .body(Full::new(Bytes::from(reply.body.to_string())))
+        .expect("fake api response"))
+}
+
+impl State {
+    fn handle(&mut self, method: &Method, path: &str, query: &str, authorized: bool, body: Option<Value>) -> Reply {
+        let path = path.strip_prefix(PREFIX).unwrap_or(path);
+        self.requests.push(format!("{method} {path}?{query}"));
+
+        if self.rate_limited > 0 {
+            self.rate_limited -= 1;
+            return Reply {
+                retry_after: Some(1),
+                ..Reply::error(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    10000,
+                    "Rate limited. Please wait and try again.",
+                )
+            };
+        }
+        if self.unavailable > 0 {
+            self.unavailable -= 1;
+            return Reply::error(StatusCode::SERVICE_UNAVAILABLE, 10000, "Service unavailable");
+        }
+        if !authorized {
+            return Reply::error(StatusCode::FORBIDDEN, 10000, "Authentication error");
+        }
+
+        let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+        match (method, &segments[..]) {
+            (&Method::GET, ["zones"]) => {
+                let account_id = query.split('&').find_map(|param| param.strip_prefix("account.id="));
+                let zones = self
+                    .zones
+                    .iter()
+                    .filter(|zone| account_id.is_none_or(|account_id| zone["account"]["id"] == account_id))
+                    .cloned()
+                    .collect();
+                self.page(zones, query)
+            }
+            (_, ["zones", zone_id, "dns_records", ..]) if !self.zones.iter().any(|zone| zone["id"] == *zone_id) => {
+                Reply::error(
+                    StatusCode::NOT_FOUND,
+                    7003,
+                    "Could not route to /zones, perhaps your object identifier is invalid?",
+                )
+            }
+            (&Method::GET, ["zones", zone_id, "dns_records"]) => {
+                let records = self.records.get(*zone_id).cloned().unwrap_or_default();
+                self.page(records, query)
+            }
+            (&Method::POST, ["zones", zone_id, "dns_records"]) => self.create(zone_id, body),
+            (&Method::PATCH, ["zones", zone_id, "dns_records", id]) => self.update(zone_id, id, body),
+            (&Method::DELETE, ["zones", zone_id, "dns_records", id]) => self.delete(zone_id, id),
+            _ => Reply::error(StatusCode::NOT_FOUND, 7000, "No route for that URI"),
+        }
+    }
+
+    fn page(&self, results: Vec<Value>, query: &str) -> Reply {
+        let page = query
+            .split('&')
+            .find_map(|param| param.strip_prefix("page="))
+            .and_then(|page| page.parse::<usize>().ok())
+            .unwrap_or(1)
+            .max(1);
+        let total_count = results.len();
+        let page_results = results
+            .into_iter()
+            .skip((page - 1) * self.per_page)
+            .take(self.per_page)
+            .collect::<Vec<_>>();
+
+        let mut reply = Reply::ok(json!(page_results));
+        reply.body["result_info"] = json!({
+            "count": page_results.len(),
+            "page": page,
+            "per_page": self.per_page,
+            "total_count": total_count,
+            "total_pages": total_count.div_ceil(self.per_page),
+        });
+        reply
+    }
+
+    fn new_record(&mut self, fields: Value) -> Value {
+        let now = Utc::now();
+        let mut record = json!({
+            "id": format!("record{}", self.next_id),
+            "comment": null,
+            "created_on": now,
+            "meta": {},
+            "modified_on": now,
+            "proxiable": true,
+            "proxied": false,
+            "tags": [],
+            "ttl": 1,
+        });
+        self.next_id += 1;
+        merge(&mut record, fields);
+        record
+    }
+
+    fn create(&mut self, zone_id: &str, body: Option<Value>) -> Reply {
+        let Some(fields) = body.filter(|body| {
+            ["name", "type", "content"]
+                .iter()
+                .all(|key| body[key].as_str().is_some_and(|it| !it.is_empty()))
+        }) else {
+            return Reply::error(StatusCode::BAD_REQUEST, 9005, "Content for record is invalid.");
+        };
+
+        let records = self.records.entry(zone_id.to_string()).or_default();
+        let conflict = records
+            .iter()
+            .any(|record| record["name"] == fields["name"] && (record["type"] == "CNAME" || fields["type"] == "CNAME"));
+        if conflict {
+            return Reply::error(
+                StatusCode::BAD_REQUEST,
+                81053,
+                "An A, AAAA, or CNAME record with that host already exists.",
+            );
+        }
+
+        // The id in the request is ignored, cloudflare assigns its own.
+        let mut fields = fields;
+        fields.as_object_mut().expect("record fields").remove("id");
+        let record = self.new_record(fields);
+        self.records
+            .get_mut(zone_id)
+            .expect("zone records")
+            .push(record.clone());
+        Reply::ok(record)
+    }
+
+    fn update(&mut self, zone_id: &str, id: &str, body: Option<Value>) -> Reply {
+        let Some(record) = self
+            .records
+            .get_mut(zone_id)
+            .and_then(|records| records.iter_mut().find(|record| record["id"] == id))
+        else {
+            return Reply::error(StatusCode::NOT_FOUND, 81044, "Record does not exist.");
+        };
+
+        let mut fields = body.unwrap_or_else(|| json!({}));
+        if let Some(fields) = fields.as_object_mut() {
+            fields.remove("id");
+        }
+        merge(record, fields);
+        record["modified_on"] = json!(Utc::now());
+        Reply::ok(record.clone())
+    }
+
+    fn delete(&mut self, zone_id: &str, id: &str) -> Reply {
+        let Some(records) = self.records.get_mut(zone_id) else {
+            return Reply::error(StatusCode::NOT_FOUND, 81044, "Record does not exist.");
+        };
+        let len = records.len();
+        records.retain(|record| record["id"] != id);
+        if records.len() == len {
+            return Reply::error(StatusCode::NOT_FOUND, 81044, "Record does not exist.");
+        }
+        Reply::ok(json!({ "id": id }))
+    }
+}
+
+/// Sets all fields of `fields` on `record`.
+fn merge(record: &mut Value, fields: Value) {
+    if let (Some(record), Value::Object(fields)) = (record.as_object_mut(), fields) {
+        record.extend(fields);
+    }
+}
+
+/// A zone as the API lists it.
+fn zone(id: &str, name: &str) -> Value {
+    let now = Utc::now();
+    json!({
+        "account": { "id": "account1", "name": "Test account" },
+        "id": id,
+        "name": name,
+        "activated_on": now,
+        "created_on": now,
+        "modified_on": now,
+        "development_mode": 0,
+        "meta": {},
+        "name_servers": ["ns1.example.net", "ns2.example.net"],
+        "original_dnshost": null,
+        "original_name_servers": null,
+        "original_registrar": null,
+        "owner": { "email": null, "id": null, "type": "user" },
+        "paused": false,
+        "permissions": ["#dns_records:edit"],
+        "plan": {
+            "can_subscribe": false,
+            "currency": "USD",
+            "externally_managed": false,
+            "frequency": "",
+            "id": "free",
+            "is_subscribed": false,
+            "legacy_discount": false,
+            "legacy_id": "free",
+            "name": "Free Website",
+            "price": 0,
+        },
+        "status": "active",
+        "tenant": {},
+        "tenant_unit": {},
+        "type": "full",
+    })
+}
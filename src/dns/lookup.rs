@@ -1,4 +1,24 @@
-use crate::resources::RecordType;
+use super::wire;
+use crate::{
+    record_data::{
+        decode_loc_size,
+        CaaData,
+        CertData,
+        DsData,
+        LatitudeDirection,
+        LocData,
+        LongitudeDirection,
+        NaptrData,
+        RecordData,
+        SrvData,
+        SshfpData,
+        SvcbData,
+        TlsaData,
+        UriData,
+    },
+    resources::RecordType,
+};
+use base64::prelude::*;
 use eyre::{
     bail,
     Result,
@@ -14,10 +34,12 @@ use rsdns::{
             Cname,
             Mx,
             Ns,
+            Ptr,
             Txt,
             A,
         },
         Class,
+        Type,
     },
 };
 use std::{
@@ -82,6 +104,21 @@ async fn get_a_records(qname: &str, nameserver: SocketAddr) -> Result<Vec<A>> {
     Ok(rrset.rdata)
 }
 
+/// Resolve the TXT records of `qname` in the given class using the specified nameserver, e.g. `whoami.cloudflare` in
+/// the CH class to discover the public IP.
+pub async fn resolve_txt(qname: &str, class: Class, nameserver: SocketAddr) -> rsdns::Result<Vec<String>> {
+    debug!(?qname, ?class, "DNS TXT lookup...");
+
+    let config = ClientConfig::with_nameserver(nameserver);
+    let mut client = Client::new(config).await?;
+    let result = client.query_rrset::<Txt>(qname, class).await?;
+    Ok(result
+        .rdata
+        .iter()
+        .map(|txt| String::from_utf8_lossy(&txt.text).to_string())
+        .collect())
+}
+
 /// Resolve a DNS record using the specified nameserver. Will stringify the result according to [RFC 1035](https://datatracker.ietf.org/doc/html/rfc1035).
 pub async fn resolve(qname: &str, ty: RecordType, nameserver: SocketAddr) -> rsdns::Result<Option<Vec<String>>> {
     debug!(?qname, ?ty, "DNS record lookup...");
@@ -126,11 +163,162 @@ pub async fn resolve(qname: &str, ty: RecordType, nameserver: SocketAddr) -> rsd
             result.rdata.iter().map(|mx| mx.nsdname.to_string()).collect()
         }
 
+        RecordType::PTR => {
+            let result = client.query_rrset::<Ptr>(qname, Class::IN).await?;
+            result.rdata.iter().map(|ptr| ptr.ptrdname.to_string()).collect()
+        }
+
         ty => {
-            error!(?ty, "Cannot resolve this record type");
-            return Ok(None);
+            let Some(rtype) = raw_type(ty) else {
+                error!(?ty, "Cannot resolve this record type");
+                return Ok(None);
+            };
+            resolve_raw(&mut client, qname, ty, rtype).await?
         }
     };
 
     Ok(Some(result))
 }
+
+/// The type code of record types rsdns has no record data for.
+fn raw_type(ty: RecordType) -> Option<Type> {
+    let code = match ty {
+        RecordType::SRV => 33,
+        RecordType::LOC => 29,
+        RecordType::NAPTR => 35,
+        RecordType::CERT => 37,
+        RecordType::DS => 43,
+        RecordType::SSHFP => 44,
+        RecordType::TLSA => 52,
+        RecordType::SVCB => 64,
+        RecordType::HTTPS => 65,
+        RecordType::URI => 256,
+        RecordType::CAA => 257,
+        _ => return None,
+    };
+    Some(Type::from(code))
+}
+
+/// Resolves records by parsing the raw response, formatted in zone file format like [`RecordData`] does.
+async fn resolve_raw(client: &mut Client, qname: &str, ty: RecordType, rtype: Type) -> rsdns::Result<Vec<String>> {
+    let mut buf = vec![0; u16::MAX as usize];
+    let len = client.query_raw(qname, rtype, Class::IN, &mut buf).await?;
+
+    let text = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+
+    let mut result = Vec::new();
+    for mut rr in wire::answers(&buf[..len], rtype)? {
+        let data = match ty {
+            RecordType::SRV => RecordData::Srv(SrvData {
+                priority: rr.u16()?,
+                weight: rr.u16()?,
+                port: rr.u16()?,
+                target: rr.name()?,
+            }),
+            RecordType::CAA => {
+                let flags = rr.u8()?;
+                let tag_len = rr.u8()?;
+                RecordData::Caa(CaaData {
+                    flags,
+                    tag: text(rr.bytes(tag_len as usize)?),
+                    value: text(rr.rest()),
+                })
+            }
+            RecordType::HTTPS | RecordType::SVCB => {
+                let svcb = SvcbData {
+                    priority: rr.u16()?,
+                    target: rr.name()?,
+                    value: rr.svc_params()?,
+                };
+                if ty == RecordType::HTTPS {
+                    RecordData::Https(svcb)
+                } else {
+                    RecordData::Svcb(svcb)
+                }
+            }
+            RecordType::TLSA => RecordData::Tlsa(TlsaData {
+                usage: rr.u8()?,
+                selector: rr.u8()?,
+                matching_type: rr.u8()?,
+                certificate: hex(rr.rest()),
+            }),
+            RecordType::DS => RecordData::Ds(DsData {
+                key_tag: rr.u16()?,
+                algorithm: rr.u8()?,
+                digest_type: rr.u8()?,
+                digest: hex(rr.rest()),
+            }),
+            RecordType::SSHFP => RecordData::Sshfp(SshfpData {
+                algorithm: rr.u8()?,
+                ty: rr.u8()?,
+                fingerprint: hex(rr.rest()),
+            }),
+            RecordType::NAPTR => RecordData::Naptr(NaptrData {
+                order: rr.u16()?,
+                preference: rr.u16()?,
+                flags: rr.character_string()?,
+                service: rr.character_string()?,
+                regex: rr.character_string()?,
+                replacement: rr.name()?,
+            }),
+            RecordType::CERT => RecordData::Cert(CertData {
+                ty: rr.u16()?,
+                key_tag: rr.u16()?,
+                algorithm: rr.u8()?,
+                certificate: BASE64_STANDARD.encode(rr.rest()),
+            }),
+            RecordType::URI => RecordData::Uri(UriData {
+                priority: rr.u16()?,
+                weight: rr.u16()?,
+                target: text(rr.rest()),
+            }),
+            RecordType::LOC => {
+                let version = rr.u8()?;
+                if version != 0 {
+                    warn!(?qname, version, "Ignoring LOC record of unknown version");
+                    continue;
+                }
+                let (size, precision_horz, precision_vert) = (rr.u8()?, rr.u8()?, rr.u8()?);
+                let (lat_degrees, lat_minutes, lat_seconds, north) = loc_coordinate(rr.u32()?);
+                let (long_degrees, long_minutes, long_seconds, east) = loc_coordinate(rr.u32()?);
+                RecordData::Loc(LocData {
+                    lat_degrees,
+                    lat_minutes,
+                    lat_seconds,
+                    lat_direction: if north {
+                        LatitudeDirection::N
+                    } else {
+                        LatitudeDirection::S
+                    },
+                    long_degrees,
+                    long_minutes,
+                    long_seconds,
+                    long_direction: if east {
+                        LongitudeDirection::E
+                    } else {
+                        LongitudeDirection::W
+                    },
+                    altitude: rr.u32()? as f64 / 100.0 - 100_000.0,
+                    size: decode_loc_size(size),
+                    precision_horz: decode_loc_size(precision_horz),
+                    precision_vert: decode_loc_size(precision_vert),
+                })
+            }
+            _ => unreachable!("no raw type for {ty:?}"),
+        };
+        result.push(data.to_string());
+    }
+    Ok(result)
+}
+
+/// Splits a LOC latitude or longitude, stored as thousandths of an arc second offset by 2^31, into degrees, minutes,
+/// seconds and whether it is north / east of the equator / prime meridian.
+fn loc_coordinate(raw: u32) -> (u8, u8, f64, bool) {
+    let offset = raw as i64 - (1 << 31);
+    let millis = offset.unsigned_abs();
+    let degrees = (millis / 3_600_000) as u8;
+    let minutes = (millis % 3_600_000 / 60_000) as u8;
+    let seconds = (millis % 60_000) as f64 / 1000.0;
+    (degrees, minutes, seconds, offset >= 0)
+}
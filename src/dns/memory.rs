@@ -0,0 +1,295 @@
+//! A [`DnsProvider`] keeping zones and records in memory, for testing reconciliation without a DNS backend.
+
+use super::{
+    cloudflare::{
+        CreateRecordArgs,
+        DnsRecordInfo,
+        DnsRecordMeta,
+        DnsRecordPatch,
+    },
+    provider::{
+        DnsProvider,
+        ZoneInfo,
+    },
+    util,
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use eyre::{
+    eyre,
+    Result,
+};
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+};
+
+/// Records are stored per zone id. Ids of new records are random, like cloudflare generates them.
+#[derive(Debug, Default)]
+pub struct InMemoryProvider {
+    zones: Vec<ZoneInfo>,
+    records: Mutex<HashMap<String, Vec<DnsRecordInfo>>>,
+}
+
+impl InMemoryProvider {
+    /// A provider hosting the given (empty) zones.
+    pub fn new(zones: impl IntoIterator<Item = ZoneInfo>) -> Self {
+        Self {
+            zones: zones.into_iter().collect(),
+            records: Default::default(),
+        }
+    }
+
+    /// All records of a zone, in the order they were created.
+    pub fn records(&self, zone_identifier: &str) -> Vec<DnsRecordInfo> {
+        self.records
+            .lock()
+            .expect("records lock")
+            .get(zone_identifier)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn zone(&self, zone_identifier: &str) -> Result<&ZoneInfo> {
+        self.zones
+            .iter()
+            .find(|zone| zone.id == zone_identifier)
+            .ok_or_else(|| eyre!("zone {zone_identifier:?} not found"))
+    }
+}
+
+#[async_trait]
+impl DnsProvider for InMemoryProvider {
+    async fn list_zones(&self) -> Result<Vec<ZoneInfo>> {
+        Ok(self.zones.clone())
+    }
+
+    async fn list_dns_records(&self, zone_identifier: &str) -> Result<Vec<DnsRecordInfo>> {
+        self.zone(zone_identifier)?;
+        Ok(self.records(zone_identifier))
+    }
+
+    async fn create_dns_record(&self, args: CreateRecordArgs) -> Result<DnsRecordInfo> {
+        let Some(zone_identifier) = args.zone.clone().lookup_id(self).await? else {
+            eyre::bail!("zone not found");
+        };
+        self.zone(&zone_identifier)?;
+
+        let now = Utc::now();
+        let record = DnsRecordInfo {
+            comment: args.marked_comment(),
+            content: args.content,
+            created_on: now,
+            id: util::id(),
+            meta: DnsRecordMeta {
+                auto_added: None,
+                managed_by_apps: None,
+                managed_by_argo_tunnel: None,
+            },
+            modified_on: now,
+            name: args.name,
+            proxiable: true,
+            proxied: args.proxied.unwrap_or_default(),
+            tags: args.tags.unwrap_or_default(),
+            // 1 is "automatic"
+            ttl: args.ttl.unwrap_or(1),
+            record_type: args.record_type.as_str().to_string(),
+            priority: args.priority,
+            data: args.data,
+        };
+        let mut records = self.records.lock().expect("records lock");
+        let records = records.entry(zone_identifier).or_default();
+        // Like cloudflare, a CNAME cannot coexist with other records of the same name.
+        let conflict = records.iter().any(|existing| {
+            existing.name == record.name && (existing.record_type == "CNAME" || record.record_type == "CNAME")
+        });
+        if conflict {
+            eyre::bail!("a record named {:?} already exists next to a CNAME", record.name);
+        }
+        records.push(record.clone());
+
+        Ok(record)
+    }
+
+    async fn update_dns_record(&self, zone_identifier: &str, id: &str, patch: DnsRecordPatch) -> Result<DnsRecordInfo> {
+        let mut records = self.records.lock().expect("records lock");
+        let record = records
+            .get_mut(zone_identifier)
+            .and_then(|records| records.iter_mut().find(|record| record.id == id))
+            .ok_or_else(|| eyre!("dns record {id:?} not found"))?;
+
+        let DnsRecordPatch {
+            name,
+            record_type,
+            content,
+            priority,
+            data,
+            ttl,
+            proxied,
+            comment,
+            tags,
+        } = patch;
+        if let Some(name) = name {
+            record.name = name;
+        }
+        if let Some(record_type) = record_type {
+            record.record_type = record_type.as_str().to_string();
+        }
+        if let Some(content) = content {
+            record.content = content;
+        }
+        if priority.is_some() {
+            record.priority = priority;
+        }
+        if data.is_some() {
+            record.data = data;
+        }
+        if let Some(ttl) = ttl {
+            record.ttl = ttl;
+        }
+        if let Some(proxied) = proxied {
+            record.proxied = proxied;
+        }
+        if comment.is_some() {
+            record.comment = comment;
+        }
+        if let Some(tags) = tags {
+            record.tags = tags;
+        }
+        record.modified_on = Utc::now();
+
+        Ok(record.clone())
+    }
+
+    async fn delete_dns_record(&self, zone_identifier: &str, id: &str) -> Result<()> {
+        let mut records = self.records.lock().expect("records lock");
+        let records = records.get_mut(zone_identifier).map(|records| {
+            let len = records.len();
+            records.retain(|record| record.id != id);
+            len != records.len()
+        });
+        if records != Some(true) {
+            eyre::bail!("dns record {id:?} not found");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dns::cloudflare::{
+            RecordContent,
+            RecordSetArgs,
+            Zone,
+        },
+        resources::{
+            OwnershipPolicy,
+            RecordType,
+        },
+    };
+
+    fn provider() -> InMemoryProvider {
+        InMemoryProvider::new([ZoneInfo {
+            id: "zone1".to_string(),
+            name: "example.com".to_string(),
+        }])
+    }
+
+    fn args(contents: &[&str]) -> RecordSetArgs {
+        RecordSetArgs {
+            zone: Zone::name("example.com"),
+            name: "foo.example.com".to_string(),
+            contents: contents
+                .iter()
+                .map(|content| RecordContent::new(RecordType::A, content))
+                .collect(),
+            comment: None,
+            ttl: Some(300),
+            proxied: None,
+            tags: None,
+            owner: Some("uid-1".to_string()),
+            ownership: OwnershipPolicy::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn sync_creates_patches_and_deletes_records() {
+        let provider = provider();
+
+        let synced = provider.sync_dns_records(args(&["1.2.3.4", "5.6.7.8"])).await.unwrap();
+        assert_eq!(synced.records.len(), 2);
+        assert!(synced.drift.is_empty());
+        assert_eq!(provider.records("zone1").len(), 2);
+
+        let synced = provider.sync_dns_records(args(&["1.2.3.4", "5.6.7.8"])).await.unwrap();
+        assert!(synced.drift.is_empty());
+
+        // The first record is patched in place, keeping its id, the other one is deleted.
+        let kept = provider.records("zone1")[0].id.clone();
+        let synced = provider.sync_dns_records(args(&["9.9.9.9"])).await.unwrap();
+        let records = provider.records("zone1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].content, "9.9.9.9");
+        assert_eq!(synced.drift.len(), 1);
+        assert_eq!(synced.drift[0].field, "content");
+        assert_eq!(records[0].id, kept);
+
+        assert!(provider
+            .delete_owned_dns_record("zone1", &records[0].id, "uid-1")
+            .await
+            .unwrap());
+        assert!(provider.records("zone1").is_empty());
+    }
+
+    #[tokio::test]
+    async fn sync_only_deletes_unowned_records_when_taking_over() {
+        let provider = provider();
+        for content in ["8.8.8.8", "9.9.9.9"] {
+            provider
+                .create_dns_record(CreateRecordArgs {
+                    owner: None,
+                    ..args(&[]).create_args(&RecordContent::new(RecordType::A, content))
+                })
+                .await
+                .unwrap();
+        }
+
+        // One record is adopted and patched, the other one is left alone.
+        provider.sync_dns_records(args(&["1.2.3.4"])).await.unwrap();
+        let records = provider.records("zone1");
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].content, "1.2.3.4");
+        assert_eq!(records[1].content, "9.9.9.9");
+
+        provider
+            .sync_dns_records(RecordSetArgs {
+                ownership: OwnershipPolicy::Takeover,
+                ..args(&["1.2.3.4"])
+            })
+            .await
+            .unwrap();
+        assert_eq!(provider.records("zone1").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_refuses_to_take_over_foreign_records() {
+        let provider = provider();
+        let foreign = provider
+            .create_dns_record(CreateRecordArgs {
+                owner: Some("uid-2".to_string()),
+                ..args(&[]).create_args(&RecordContent::new(RecordType::A, "1.2.3.4"))
+            })
+            .await
+            .unwrap();
+
+        assert!(provider.sync_dns_records(args(&["5.6.7.8"])).await.is_err());
+        assert!(!provider
+            .delete_owned_dns_record("zone1", &foreign.id, "uid-1")
+            .await
+            .unwrap());
+        assert_eq!(provider.records("zone1").len(), 1);
+        assert_eq!(provider.records("zone1")[0].content, "1.2.3.4");
+    }
+}
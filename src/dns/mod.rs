@@ -1,3 +1,10 @@
 pub mod cloudflare;
+#[cfg(test)]
+pub(crate) mod fake_api;
 pub(crate) mod lookup;
+pub mod memory;
+pub mod ownership;
+pub mod provider;
+pub mod rate_limit;
 mod util;
+mod wire;
@@ -0,0 +1,132 @@
+//! Tracks which cloudflare dns records were created by the operator. Records carry a marker with the UID of the
+//! owning `CloudflareDNSRecord` resource at the end of their comment, e.g.
+//! `Managed by the Cloudflare DNS Operator [cloudflare-dns-operator:7b2e...]`.
+
+use super::cloudflare::DnsRecordInfo;
+use crate::resources::OwnershipPolicy;
+
+const MARKER_PREFIX: &str = "[cloudflare-dns-operator:";
+const MARKER_SUFFIX: &str = "]";
+
+/// The maximum length of a record comment on cloudflare's free plan.
+pub const MAX_COMMENT_LEN: usize = 100;
+
+/// Who owns a cloudflare dns record from the point of view of a `CloudflareDNSRecord` resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ownership {
+    /// The record carries the marker of the resource.
+    Owned,
+    /// The record carries no marker, it was e.g. created manually.
+    Unowned,
+    /// The record carries the marker of another resource.
+    Foreign,
+}
+
+impl Ownership {
+    pub fn of(record: &DnsRecordInfo, owner: &str) -> Self {
+        match owner_of(record.comment.as_deref()) {
+            Some(it) if it == owner => Ownership::Owned,
+            Some(_) => Ownership::Foreign,
+            None => Ownership::Unowned,
+        }
+    }
+
+    /// Whether the operator may modify or delete a record with this ownership under `policy`.
+    pub fn is_manageable(self, policy: OwnershipPolicy) -> bool {
+        matches!(
+            (self, policy),
+            (Ownership::Owned, _)
+                | (Ownership::Unowned, OwnershipPolicy::Adopt | OwnershipPolicy::Takeover)
+                | (Ownership::Foreign, OwnershipPolicy::Takeover)
+        )
+    }
+}
+
+/// Extracts the owner UID from a record comment.
+pub fn owner_of(comment: Option<&str>) -> Option<&str> {
+    let comment = comment?.strip_suffix(MARKER_SUFFIX)?;
+    let start = comment.rfind(MARKER_PREFIX)?;
+    Some(&comment[start + MARKER_PREFIX.len()..])
+}
+
+/// Removes the owner marker from a record comment, returning the user specified part.
+pub fn strip_marker(comment: Option<&str>) -> Option<&str> {
+    let comment = comment?;
+    let stripped = match owner_of(Some(comment)) {
+        Some(_) => comment[..comment.rfind(MARKER_PREFIX).expect("marker")].trim_end(),
+        None => comment,
+    };
+    (!stripped.is_empty()).then_some(stripped)
+}
+
+/// Appends the owner marker to a record comment, cut off by [`truncate_comment`].
+pub fn with_marker(comment: Option<&str>, owner: &str) -> String {
+    match comment.map(|comment| truncate_comment(comment, owner)) {
+        Some(comment) if !comment.is_empty() => format!("{comment} {MARKER_PREFIX}{owner}{MARKER_SUFFIX}"),
+        _ => format!("{MARKER_PREFIX}{owner}{MARKER_SUFFIX}"),
+    }
+}
+
+/// The part of `comment` that fits into [`MAX_COMMENT_LEN`] together with the marker of `owner`. Cloudflare rejects
+/// longer comments, so the rest is cut off.
+pub fn truncate_comment<'a>(comment: &'a str, owner: &str) -> &'a str {
+    let max = MAX_COMMENT_LEN.saturating_sub(MARKER_PREFIX.len() + owner.len() + MARKER_SUFFIX.len() + 1);
+    if comment.len() <= max {
+        return comment;
+    }
+    let mut end = max;
+    while !comment.is_char_boundary(end) {
+        end -= 1;
+    }
+    comment[..end].trim_end()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_roundtrip() {
+        let comment = with_marker(Some("Managed by k8s"), "uid-1");
+        assert_eq!(comment, "Managed by k8s [cloudflare-dns-operator:uid-1]");
+        assert_eq!(owner_of(Some(&comment)), Some("uid-1"));
+        assert_eq!(strip_marker(Some(&comment)), Some("Managed by k8s"));
+
+        let comment = with_marker(None, "uid-1");
+        assert_eq!(owner_of(Some(&comment)), Some("uid-1"));
+        assert_eq!(strip_marker(Some(&comment)), None);
+    }
+
+    #[test]
+    fn long_comments_are_cut_off_before_the_marker() {
+        let owner = "7b2e3c4d-1111-2222-3333-444455556666";
+        let comment = with_marker(Some(&"ä".repeat(100)), owner);
+        assert!(comment.len() <= MAX_COMMENT_LEN, "{comment}");
+        assert_eq!(owner_of(Some(&comment)), Some(owner));
+        assert_eq!(
+            strip_marker(Some(&comment)),
+            Some(truncate_comment(&"ä".repeat(100), owner))
+        );
+        assert_eq!(truncate_comment("short", owner), "short");
+    }
+
+    #[test]
+    fn comments_without_marker_are_unowned() {
+        assert_eq!(owner_of(None), None);
+        assert_eq!(owner_of(Some("google-site-verification")), None);
+        assert_eq!(
+            strip_marker(Some("google-site-verification")),
+            Some("google-site-verification")
+        );
+    }
+
+    #[test]
+    fn policies() {
+        use OwnershipPolicy::*;
+        assert!(Ownership::Owned.is_manageable(Refuse));
+        assert!(!Ownership::Unowned.is_manageable(Refuse));
+        assert!(Ownership::Unowned.is_manageable(Adopt));
+        assert!(!Ownership::Foreign.is_manageable(Adopt));
+        assert!(Ownership::Foreign.is_manageable(Takeover));
+    }
+}
@@ -0,0 +1,223 @@
+//! The interface to DNS backends. Reconciliation only talks to a [`DnsProvider`], cloudflare is implemented by
+//! [`CloudflareApi`](super::cloudflare::CloudflareApi). Managing the record set of a name is built on top of the
+//! basic record operations every provider implements.
+
+use super::{
+    cloudflare::{
+        plan_record_set,
+        CreateRecordArgs,
+        DnsRecordInfo,
+        DnsRecordPatch,
+        RecordSetArgs,
+        SyncedRecords,
+    },
+    ownership::Ownership,
+};
+use crate::resources::RecordType;
+use async_trait::async_trait;
+use eyre::{
+    bail,
+    Context as _,
+    Result,
+};
+
+/// A DNS zone of a provider.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZoneInfo {
+    pub id: String,
+    /// The domain of the zone, such as "example.com".
+    pub name: String,
+}
+
+/// A backend hosting DNS zones. Implementations only need to provide listing, creating, updating and deleting of
+/// records, higher level operations such as [`DnsProvider::sync_dns_records`] are built on them.
+#[async_trait]
+pub trait DnsProvider: Send + Sync {
+    /// List all zones accessible to the provider.
+    async fn list_zones(&self) -> Result<Vec<ZoneInfo>>;
+
+    /// List all DNS records in a zone.
+    async fn list_dns_records(&self, zone_identifier: &str) -> Result<Vec<DnsRecordInfo>>;
+
+    /// Create a new DNS record.
+    async fn create_dns_record(&self, args: CreateRecordArgs) -> Result<DnsRecordInfo>;
+
+    /// Patch an existing DNS record in place. The record keeps its id.
+    async fn update_dns_record(&self, zone_identifier: &str, id: &str, patch: DnsRecordPatch) -> Result<DnsRecordInfo>;
+
+    /// Delete a DNS record by its id.
+    async fn delete_dns_record(&self, zone_identifier: &str, id: &str) -> Result<()>;
+
+    /// Makes sure exactly one dns record exists for each of [`RecordSetArgs::contents`] under the name of `args`.
+    /// Existing records are patched in place where possible so the name keeps resolving while contents change, missing
+    /// records are created and superfluous ones are deleted. All managed attributes of existing records are compared
+    /// against `args` and corrected if they have drifted.
+    ///
+    /// Existing records are only modified if [`RecordSetArgs::ownership`] allows it. Superfluous records are only
+    /// deleted if they are owned by [`RecordSetArgs::owner`] or the policy is `takeover`, records of types not in
+    /// `contents` only if they are owned.
+    #[instrument(level = "debug", skip(self))]
+    async fn sync_dns_records(&self, args: RecordSetArgs) -> Result<SyncedRecords> {
+        let Some(zone_id) = args.zone.clone().lookup_id(self).await? else {
+            bail!("zone not found");
+        };
+
+        debug!("syncing dns records");
+
+        let domain = args.name.as_str();
+        let (same_type, other_type): (Vec<_>, Vec<_>) = self
+            .list_dns_records(&zone_id)
+            .await?
+            .into_iter()
+            .filter(|record| record.name == domain)
+            .partition(|record| {
+                args.contents
+                    .iter()
+                    .any(|content| content.record_type.as_str() == record.record_type)
+            });
+
+        let (manageable, unmanageable): (Vec<_>, Vec<_>) =
+            same_type.into_iter().partition(|record| args.may_manage(record));
+
+        if !unmanageable.is_empty() {
+            bail!(
+                "refusing to modify existing DNS record(s) not owned by this resource (ownership policy {:?}): {}",
+                args.ownership,
+                unmanageable
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
+        let plan = plan_record_set(&args.contents, manageable);
+        let mut records = Vec::with_capacity(args.contents.len());
+        let mut drift = Vec::new();
+
+        // Adopted records are kept when no longer needed, unless taking over is allowed.
+        let (mut superfluous, kept): (Vec<_>, Vec<_>) =
+            plan.superfluous.into_iter().partition(|record| args.may_delete(record));
+        for record in kept {
+            info!("Keeping DNS record {record} that was not created by this resource");
+        }
+        // Leftovers of our own of other types, e.g. after the record type of the spec changed.
+        superfluous.extend(other_type.into_iter().filter(|record| args.owns(record)));
+
+        // Usually update and create first, then delete, so that the name keeps resolving. But a CNAME cannot coexist
+        // with other records of the same name, so room has to be made for it first.
+        let delete_first = args
+            .contents
+            .iter()
+            .any(|content| content.record_type == RecordType::CNAME)
+            || superfluous
+                .iter()
+                .any(|record| record.record_type == RecordType::CNAME.as_str());
+        if delete_first {
+            delete_superfluous_dns_records(self, &zone_id, std::mem::take(&mut superfluous)).await?;
+        }
+
+        for (content, existing) in args.contents.iter().zip(plan.existing) {
+            let Some(existing) = existing else {
+                info!("Creating new DNS record for {domain:?} with {content:?}");
+                let record = self.create_dns_record(args.create_args(content)).await?;
+                debug!("Registered record for {domain:?} with {:?}", record.content);
+                records.push(record);
+                continue;
+            };
+
+            let record_drift = args.diff(content, &existing);
+            if record_drift.is_empty() {
+                info!("DNS record for {domain:?} already exists with {content:?}");
+                records.push(existing);
+                continue;
+            }
+
+            info!(
+                "Updating DNS record {existing}, drifted: {}",
+                record_drift
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            let record = self
+                .update_dns_record(
+                    &zone_id,
+                    &existing.id,
+                    DnsRecordPatch {
+                        content: Some(content.content.clone()),
+                        priority: content.priority,
+                        data: content.data.clone(),
+                        ttl: args.ttl,
+                        proxied: args.proxied,
+                        comment: args.marked_comment(Some(&existing)),
+                        tags: args.tags.clone(),
+                        ..Default::default()
+                    },
+                )
+                .await
+                .context("Failed to update existing DNS record")?;
+            records.push(record);
+            drift.extend(record_drift);
+        }
+
+        delete_superfluous_dns_records(self, &zone_id, superfluous).await?;
+
+        Ok(SyncedRecords { records, drift })
+    }
+
+    /// Delete a DNS record by its id, but only if it is marked as owned by `owner`. Returns whether the record was
+    /// deleted.
+    async fn delete_owned_dns_record(&self, zone_identifier: &str, id: &str, owner: &str) -> Result<bool> {
+        let Some(record) = self
+            .list_dns_records(zone_identifier)
+            .await?
+            .into_iter()
+            .find(|record| record.id == id)
+        else {
+            debug!(?id, "dns record to delete does not exist");
+            return Ok(false);
+        };
+
+        if Ownership::of(&record, owner) != Ownership::Owned {
+            warn!("Not deleting DNS record {record}, it is not owned by {owner:?}");
+            return Ok(false);
+        }
+
+        self.delete_dns_record(zone_identifier, id).await?;
+
+        Ok(true)
+    }
+
+    /// Delete DNS records by their (domain) name. Does not check ownership, all records with that name are deleted.
+    async fn delete_dns_records_by_name(&self, name: &str, zone_identifier: &str) -> Result<()> {
+        info!(?name, "deleting dns record by name");
+        let records = self
+            .list_dns_records(zone_identifier)
+            .await?
+            .into_iter()
+            .filter(|it| it.name == name);
+
+        for record in records {
+            self.delete_dns_record(zone_identifier, &record.id).await?;
+        }
+
+        Ok(())
+    }
+}
+
+async fn delete_superfluous_dns_records(
+    provider: &(impl DnsProvider + ?Sized),
+    zone_identifier: &str,
+    records: Vec<DnsRecordInfo>,
+) -> Result<()> {
+    for record in records {
+        warn!("Found superfluous DNS record {record}. Deleting.");
+        provider
+            .delete_dns_record(zone_identifier, &record.id)
+            .await
+            .context("Failed to delete existing DNS record")?;
+    }
+    Ok(())
+}
@@ -0,0 +1,159 @@
+//! Client side rate limiting of cloudflare API requests. Cloudflare allows 1200 requests per 5 minutes per user and
+//! blocks all requests for the rest of the window once that is exceeded, see
+//! <https://developers.cloudflare.com/fundamentals/api/reference/limits/>. Requests therefore take a token from a
+//! bucket shared by all reconciles before they are sent, and a 429 response pauses all requests.
+
+use std::{
+    sync::Mutex,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+
+/// Requests allowed per [`WINDOW`].
+pub const REQUESTS_PER_WINDOW: u32 = 1200;
+pub const WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Delay before the first retry of a failed request, doubled for every further attempt.
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(30);
+
+/// A token bucket shared by all requests of a [`CloudflareApi`](super::cloudflare::CloudflareApi) and of other clients
+/// using the same token, see [`CloudflareApi::with_rate_limiter`](super::cloudflare::CloudflareApi::with_rate_limiter).
+#[derive(Debug)]
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// Tokens added per second.
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl Bucket {
+    /// Takes a token if one is available at `now`, otherwise returns how long to wait before trying again.
+    fn take(&mut self, now: Instant) -> Result<(), Duration> {
+        if let Some(until) = self.paused_until.filter(|until| *until > now) {
+            return Err(until - now);
+        }
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.rate))
+        }
+    }
+
+    fn pause(&mut self, now: Instant, duration: Duration) {
+        let until = now + duration;
+        self.paused_until = Some(self.paused_until.map_or(until, |paused| paused.max(until)));
+        self.tokens = 0.0;
+        self.updated = until;
+    }
+}
+
+impl RateLimiter {
+    /// Allows bursts of up to `requests` requests and `requests` per `window` on average.
+    pub fn new(requests: u32, window: Duration) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                capacity: requests as f64,
+                rate: requests as f64 / window.as_secs_f64(),
+                tokens: requests as f64,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = match self.bucket.lock().expect("rate limiter lock").take(Instant::now()) {
+                Ok(()) => return,
+                Err(wait) => wait,
+            };
+            debug!("cloudflare api rate limit reached, waiting {wait:?}");
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Holds back all requests for `duration`, e.g. after cloudflare answered with 429 Too Many Requests.
+    pub fn pause(&self, duration: Duration) {
+        self.bucket
+            .lock()
+            .expect("rate limiter lock")
+            .pause(Instant::now(), duration);
+    }
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(REQUESTS_PER_WINDOW, WINDOW)
+    }
+}
+
+/// Exponential backoff before retry number `attempt` (starting at 0), with jitter so that concurrent reconciles do not
+/// retry in lockstep.
+pub fn backoff(attempt: u32) -> Duration {
+    let delay = BACKOFF_BASE
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(BACKOFF_MAX);
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_refills_over_time_and_pauses() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            capacity: 2.0,
+            rate: 1.0,
+            tokens: 2.0,
+            updated: start,
+            paused_until: None,
+        };
+
+        assert_eq!(bucket.take(start), Ok(()));
+        assert_eq!(bucket.take(start), Ok(()));
+        assert_eq!(bucket.take(start), Err(Duration::from_secs(1)));
+        let later = start + Duration::from_millis(1500);
+        assert_eq!(bucket.take(later), Ok(()));
+        assert_eq!(bucket.take(later), Err(Duration::from_millis(500)));
+
+        // Refilling stops at the capacity.
+        let much_later = later + Duration::from_secs(60);
+        assert_eq!(bucket.take(much_later), Ok(()));
+        assert_eq!(bucket.take(much_later), Ok(()));
+        assert!(bucket.take(much_later).is_err());
+
+        bucket.pause(much_later, Duration::from_secs(10));
+        assert_eq!(
+            bucket.take(much_later + Duration::from_secs(4)),
+            Err(Duration::from_secs(6))
+        );
+        assert_eq!(bucket.take(much_later + Duration::from_secs(11)), Ok(()));
+    }
+
+    #[test]
+    fn backoff_grows_with_jitter() {
+        for attempt in 0..10 {
+            let delay = backoff(attempt);
+            let full = BACKOFF_BASE.saturating_mul(2u32.pow(attempt)).min(BACKOFF_MAX);
+            assert!(delay >= full / 2 && delay <= full, "{attempt}: {delay:?}");
+        }
+    }
+}
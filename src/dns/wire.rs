@@ -0,0 +1,254 @@
+//! Parsing of record data rsdns has no types for, read from raw DNS responses. Only the answer section is considered.
+
+use crate::record_data::SVC_PARAM_KEYS;
+use base64::prelude::*;
+use rsdns::{
+    message::{
+        reader::MessageReader,
+        RecordsSection,
+    },
+    records::Type,
+    Error,
+    Result,
+};
+use std::net::{
+    Ipv4Addr,
+    Ipv6Addr,
+};
+
+/// Maximum number of compression pointers followed while reading a name.
+const MAX_POINTERS: usize = 32;
+
+/// Record data of an answer, positioned within the message so that compressed names can be read.
+pub struct RecordData<'a> {
+    msg: &'a [u8],
+    pos: usize,
+    end: usize,
+}
+
+impl<'a> RecordData<'a> {
+    pub fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.pos + len > self.end {
+            return Err(Error::EndOfBuffer);
+        }
+        let bytes = &self.msg[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// The remaining record data.
+    pub fn rest(&mut self) -> &'a [u8] {
+        let bytes = &self.msg[self.pos..self.end];
+        self.pos = self.end;
+        bytes
+    }
+
+    /// Reads a length prefixed character string.
+    pub fn character_string(&mut self) -> Result<String> {
+        let len = self.u8()?;
+        Ok(String::from_utf8_lossy(self.bytes(len as usize)?).into_owned())
+    }
+
+    /// Reads a domain name, without the trailing dot. The root name is returned as `.`.
+    pub fn name(&mut self) -> Result<String> {
+        let (name, next) = read_name(self.msg, self.pos)?;
+        if next > self.end {
+            return Err(Error::EndOfBuffer);
+        }
+        self.pos = next;
+        Ok(if name.is_empty() { ".".to_string() } else { name })
+    }
+
+    /// Reads the remaining data as SVCB / HTTPS service parameters ([RFC 9460](https://www.rfc-editor.org/rfc/rfc9460))
+    /// in zone file format, e.g. `alpn="h3,h2" ipv4hint="1.2.3.4"`.
+    pub fn svc_params(&mut self) -> Result<String> {
+        let mut params = Vec::new();
+        while self.pos < self.end {
+            let key = self.u16()?;
+            let len = self.u16()?;
+            let mut value = RecordData {
+                msg: self.msg,
+                pos: self.pos,
+                end: self.pos + len as usize,
+            };
+            self.bytes(len as usize)?;
+
+            let mut values = Vec::new();
+            match key {
+                0 => {
+                    while value.pos < value.end {
+                        values.push(svc_param_key(value.u16()?));
+                    }
+                }
+                1 => {
+                    while value.pos < value.end {
+                        values.push(value.character_string()?);
+                    }
+                }
+                3 => values.push(value.u16()?.to_string()),
+                4 => {
+                    while value.pos < value.end {
+                        let octets: [u8; 4] = value.bytes(4)?.try_into().expect("4 bytes");
+                        values.push(Ipv4Addr::from(octets).to_string());
+                    }
+                }
+                5 => values.push(BASE64_STANDARD.encode(value.rest())),
+                6 => {
+                    while value.pos < value.end {
+                        let octets: [u8; 16] = value.bytes(16)?.try_into().expect("16 bytes");
+                        values.push(Ipv6Addr::from(octets).to_string());
+                    }
+                }
+                _ => values.push(String::from_utf8_lossy(value.rest()).into_owned()),
+            }
+
+            let name = svc_param_key(key);
+            if len == 0 {
+                params.push(name);
+            } else {
+                params.push(format!("{name}=\"{}\"", values.join(",")));
+            }
+        }
+        Ok(params.join(" "))
+    }
+}
+
+fn svc_param_key(key: u16) -> String {
+    match SVC_PARAM_KEYS.get(key as usize) {
+        Some(name) => name.to_string(),
+        None => format!("key{key}"),
+    }
+}
+
+/// The record data of all answers of type `ty` in the DNS response `msg`.
+pub fn answers(msg: &[u8], ty: Type) -> Result<Vec<RecordData<'_>>> {
+    let mut reader = MessageReader::new(msg)?;
+    reader.header()?;
+    reader.skip_questions()?;
+
+    let mut answers = Vec::new();
+    while reader.has_records() {
+        let marker = reader.record_marker()?;
+        if marker.section() != RecordsSection::Answer || marker.rtype() != ty {
+            reader.skip_record_data(&marker)?;
+            continue;
+        }
+        let data = reader.record_data_bytes(&marker)?;
+        let pos = data.as_ptr() as usize - msg.as_ptr() as usize;
+        answers.push(RecordData {
+            msg,
+            pos,
+            end: pos + data.len(),
+        });
+    }
+    Ok(answers)
+}
+
+/// Reads the possibly compressed name at `pos`. Returns the name and the position following it.
+fn read_name(msg: &[u8], mut pos: usize) -> Result<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut next = None;
+    let mut pointers = 0;
+
+    loop {
+        let len = *msg.get(pos).ok_or(Error::EndOfBuffer)?;
+        match len & 0b1100_0000 {
+            0 if len == 0 => break,
+            0 => {
+                let label = msg.get(pos + 1..pos + 1 + len as usize).ok_or(Error::EndOfBuffer)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                pos += 1 + len as usize;
+            }
+            0b1100_0000 => {
+                let low = *msg.get(pos + 1).ok_or(Error::EndOfBuffer)?;
+                let pointer = (((len & 0b0011_1111) as usize) << 8) | low as usize;
+                if pointer >= pos {
+                    return Err(Error::DomainNameBadPointer {
+                        pointer,
+                        max_offset: pos,
+                    });
+                }
+                pointers += 1;
+                if pointers > MAX_POINTERS {
+                    return Err(Error::DomainNameTooMuchPointers);
+                }
+                next.get_or_insert(pos + 2);
+                pos = pointer;
+            }
+            _ => return Err(Error::DomainNameBadLabelType(len)),
+        }
+    }
+
+    Ok((labels.join("."), next.unwrap_or(pos + 1)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srv_answers_with_compressed_names() {
+        #[rustfmt::skip]
+        let msg = [
+            // header: 1 question, 1 answer
+            0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0,
+            // question: _sip._tcp.example.com SRV IN
+            4, b'_', b's', b'i', b'p', 4, b'_', b't', b'c', b'p',
+            7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 3, b'c', b'o', b'm', 0,
+            0, 33, 0, 1,
+            // answer: pointer to the question name, SRV IN, ttl 300
+            0xc0, 12, 0, 33, 0, 1, 0, 0, 1, 44, 0, 12,
+            // priority 10, weight 5, port 5060, target sip + pointer to example.com
+            0, 10, 0, 5, 0x13, 0xc4, 3, b's', b'i', b'p', 0xc0, 22,
+        ];
+
+        let mut answers = answers(&msg, Type::from(33)).unwrap();
+        assert_eq!(answers.len(), 1);
+        let srv = &mut answers[0];
+        assert_eq!(srv.u16().unwrap(), 10);
+        assert_eq!(srv.u16().unwrap(), 5);
+        assert_eq!(srv.u16().unwrap(), 5060);
+        assert_eq!(srv.name().unwrap(), "sip.example.com");
+        assert!(srv.u16().is_err());
+
+        assert!(super::answers(&msg, Type::from(1)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn svc_params_in_zone_format() {
+        #[rustfmt::skip]
+        let msg = [
+            // alpn h3,h2
+            0, 1, 0, 6, 2, b'h', b'3', 2, b'h', b'2',
+            // no-default-alpn
+            0, 2, 0, 0,
+            // port 8443
+            0, 3, 0, 2, 0x20, 0xfb,
+            // ipv4hint 1.2.3.4
+            0, 4, 0, 4, 1, 2, 3, 4,
+        ];
+        let mut data = RecordData {
+            msg: &msg,
+            pos: 0,
+            end: msg.len(),
+        };
+        assert_eq!(
+            data.svc_params().unwrap(),
+            r#"alpn="h3,h2" no-default-alpn port="8443" ipv4hint="1.2.3.4""#
+        );
+    }
+}
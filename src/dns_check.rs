@@ -1,6 +1,7 @@
 use crate::{
     context::Context,
     dns::lookup as dns_lookup,
+    record_data::normalize_content,
     resources::CloudflareDNSRecord,
 };
 use futures::Stream;
@@ -73,7 +74,7 @@ pub fn start_dns_check(
 
             debug!("Checking DNS {} CloudflareDNSRecord resources", resources.len());
 
-            for resource in resources {
+            'resources: for resource in resources {
                 let Some(name) = resource.metadata.name.clone() else {
                     error!("Resource has no name: {:?}", resource);
                     continue;
@@ -93,28 +94,43 @@ pub fn start_dns_check(
 
                 let qname = &resource.spec.name;
 
-                let Some(content) = resource.spec.lookup_content(&ctx.client, &ns).await.ok().flatten() else {
+                let Some(contents) = resource.spec.lookup_contents(&ctx, &ns).await.ok().flatten() else {
                     error!("unable to resolve content for CloudflareDNSRecord {key:?}");
                     continue;
                 };
 
-                let ty = resource.spec.ty.unwrap_or_default();
-
-                let dns_record_data = match dns_lookup::resolve(qname, ty,nameserver).await {
-                    Ok(Some(it)) => it,
-                    Ok(None) => {
-                        error!("Unable to resolve unsupported DNS record type: {ty:?} for {key:?}");
-                        continue;
-                    }
-                    Err(err) => {
-                        error!("Failed to resolve DNS record: {err:?} for {key:?}");
-                        Vec::new()
+                let mut types = Vec::new();
+                for content in &contents {
+                    if !types.contains(&content.record_type) {
+                        types.push(content.record_type);
                     }
-                };
+                }
 
-                let matches = dns_record_data.contains(&content);
+                let mut matches = !contents.is_empty();
+                for ty in types {
+                    let dns_record_data = match dns_lookup::resolve(qname, ty, nameserver).await {
+                        Ok(Some(it)) => it,
+                        Ok(None) => {
+                            error!("Unable to resolve unsupported DNS record type: {ty:?} for {key:?}");
+                            continue 'resources;
+                        }
+                        Err(err) => {
+                            error!("Failed to resolve DNS record: {err:?} for {key:?}");
+                            Vec::new()
+                        }
+                    };
+
+                    let expected = contents.iter().filter(|content| content.record_type == ty);
+                    trace!(?key, ?ty, ?dns_record_data, ?contents, "Matches DNS record?");
+                    let actual = dns_record_data
+                        .iter()
+                        .map(|data| normalize_content(ty, data))
+                        .collect::<Vec<_>>();
+                    matches &= expected
+                        .into_iter()
+                        .all(|content| actual.contains(&normalize_content(ty, &content.zone_format())));
+                }
 
-                trace!(?key, ?dns_record_data, ?content, "Matches DNS record?");
                 let mut dns_lookup_success = ctx.dns_lookup_success.lock().await;
                 let matched_before = dns_lookup_success.get(&key).cloned().unwrap_or(false);
                 let changed = matched_before != matches;
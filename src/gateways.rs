@@ -0,0 +1,523 @@
+//! Support for [Gateway API](https://gateway-api.sigs.k8s.io/) resources as record content. Only the parts of the
+//! `Gateway` and `HTTPRoute` resources the operator reads are modelled here.
+
+use crate::{
+    context::Context,
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    resources::{
+        CloudflareDNSRecord,
+        GatewaySelector,
+        RecordType,
+        ResolvedContent,
+    },
+    services::{
+        resolve_addresses,
+        ServiceAddresses,
+    },
+};
+use eyre::{
+    bail,
+    Result,
+};
+use k8s_openapi::{
+    api::core::v1::Namespace,
+    apimachinery::pkg::apis::meta::v1::LabelSelector,
+};
+use kube::{
+    core::{
+        Selector,
+        SelectorExt as _,
+    },
+    runtime::reflector::ObjectRef,
+    CustomResource,
+    ResourceExt as _,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    net::IpAddr,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+const GATEWAY_API_GROUP: &str = "gateway.networking.k8s.io";
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[kube(
+    group = "gateway.networking.k8s.io",
+    version = "v1",
+    kind = "Gateway",
+    status = "GatewayStatus",
+    schema = "disabled",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct GatewaySpec {
+    pub gateway_class_name: String,
+    #[serde(default)]
+    pub listeners: Vec<Listener>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Listener {
+    pub name: String,
+    pub allowed_routes: Option<AllowedRoutes>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct AllowedRoutes {
+    pub namespaces: Option<RouteNamespaces>,
+    pub kinds: Option<Vec<RouteGroupKind>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RouteNamespaces {
+    /// `Same` (the default), `All` or `Selector`.
+    pub from: Option<String>,
+    pub selector: Option<LabelSelector>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct RouteGroupKind {
+    pub group: Option<String>,
+    pub kind: String,
+}
+
+impl Listener {
+    /// Whether an HTTPRoute in namespace `route_ns` with the labels `route_ns_labels` may attach to this listener of
+    /// a gateway in namespace `gateway_ns`.
+    fn allows_route(&self, gateway_ns: &str, route_ns: &str, route_ns_labels: &BTreeMap<String, String>) -> bool {
+        let allowed = self.allowed_routes.clone().unwrap_or_default();
+        let kind_allowed = allowed.kinds.as_ref().is_none_or(|kinds| {
+            kinds.iter().any(|kind| {
+                kind.kind == "HTTPRoute" && kind.group.as_deref().unwrap_or(GATEWAY_API_GROUP) == GATEWAY_API_GROUP
+            })
+        });
+        let namespaces = allowed.namespaces.unwrap_or_default();
+        let namespace_allowed = match namespaces.from.as_deref().unwrap_or("Same") {
+            "All" => true,
+            "Same" => route_ns == gateway_ns,
+            "Selector" => namespaces
+                .selector
+                .and_then(|selector| Selector::try_from(selector).ok())
+                .is_some_and(|selector| selector.matches(route_ns_labels)),
+            other => {
+                warn!(
+                    "Ignoring unknown allowedRoutes namespaces {other:?} of listener {:?}",
+                    self.name
+                );
+                false
+            }
+        };
+        kind_allowed && namespace_allowed
+    }
+
+    fn selects_namespaces_by_label(&self) -> bool {
+        self.allowed_routes
+            .as_ref()
+            .and_then(|allowed| allowed.namespaces.as_ref())
+            .and_then(|namespaces| namespaces.from.as_deref())
+            == Some("Selector")
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct GatewayStatus {
+    pub addresses: Option<Vec<GatewayStatusAddress>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct GatewayStatusAddress {
+    /// `IPAddress` (the default), `Hostname` or an implementation specific type.
+    #[serde(rename = "type")]
+    pub type_: Option<String>,
+    pub value: String,
+}
+
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[kube(
+    group = "gateway.networking.k8s.io",
+    version = "v1",
+    kind = "HTTPRoute",
+    schema = "disabled",
+    namespaced
+)]
+#[serde(rename_all = "camelCase")]
+pub struct HTTPRouteSpec {
+    pub parent_refs: Option<Vec<ParentReference>>,
+    pub hostnames: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ParentReference {
+    pub group: Option<String>,
+    pub kind: Option<String>,
+    pub name: String,
+    pub namespace: Option<String>,
+    /// The listener of the gateway the route attaches to, all listeners if unset.
+    pub section_name: Option<String>,
+}
+
+impl HTTPRoute {
+    /// The gateways this route is attached to.
+    fn parent_gateways(&self) -> Vec<Dependency> {
+        self.gateway_refs().map(|(gateway, _)| gateway).collect()
+    }
+
+    /// The parent references to gateways, with the gateway they resolve to.
+    fn gateway_refs(&self) -> impl Iterator<Item = (Dependency, &ParentReference)> {
+        let ns = self.namespace().unwrap_or_else(|| "default".to_string());
+        self.spec
+            .parent_refs
+            .iter()
+            .flatten()
+            .filter(|parent| {
+                parent.group.as_deref().unwrap_or(GATEWAY_API_GROUP) == GATEWAY_API_GROUP
+                    && parent.kind.as_deref().unwrap_or("Gateway") == "Gateway"
+            })
+            .map(move |parent| {
+                let gateway = Dependency::Gateway {
+                    name: parent.name.clone(),
+                    namespace: parent.namespace.clone().unwrap_or_else(|| ns.clone()),
+                };
+                (gateway, parent)
+            })
+    }
+
+    /// Whether the route is attached to `gateway` and accepted by one of the listeners it references.
+    fn is_allowed_by(&self, gateway: &Gateway, route_ns_labels: &BTreeMap<String, String>) -> bool {
+        let gateway_ns = gateway.namespace().unwrap_or_else(|| "default".to_string());
+        let route_ns = self.namespace().unwrap_or_else(|| "default".to_string());
+        let gateway_ref = Dependency::Gateway {
+            name: gateway.name_any(),
+            namespace: gateway_ns.clone(),
+        };
+        self.gateway_refs()
+            .filter(|(it, _)| *it == gateway_ref)
+            .any(|(_, parent)| {
+                gateway
+                    .spec
+                    .listeners
+                    .iter()
+                    .filter(|listener| {
+                        parent
+                            .section_name
+                            .as_ref()
+                            .is_none_or(|section| *section == listener.name)
+                    })
+                    .any(|listener| listener.allows_route(&gateway_ns, &route_ns, route_ns_labels))
+            })
+    }
+}
+
+/// Maps a change of a [`Gateway`] to all `CloudflareDNSRecord`s referencing it.
+pub fn records_referencing_gateway(index: &DependencyIndex, gateway: Gateway) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (gateway.metadata.name, gateway.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::Gateway { name, namespace })
+}
+
+/// Remembers the gateways each [`HTTPRoute`] was attached to, so that records of a gateway a route was detached from
+/// are reconciled as well.
+#[derive(Clone, Default)]
+pub struct RouteParents {
+    inner: Arc<Mutex<HashMap<ObjectRef<HTTPRoute>, Vec<Dependency>>>>,
+}
+
+/// Maps a change of an [`HTTPRoute`] to all `CloudflareDNSRecord`s referencing a gateway the route is or was attached
+/// to.
+pub fn records_referencing_route(
+    index: &DependencyIndex,
+    parents: &RouteParents,
+    route: HTTPRoute,
+) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let gateways = route.parent_gateways();
+    let previous = {
+        let mut parents = parents.inner.lock().expect("route parents lock");
+        let obj_ref = ObjectRef::from_obj(&route);
+        if route.metadata.deletion_timestamp.is_some() {
+            parents.remove(&obj_ref)
+        } else {
+            parents.insert(obj_ref, gateways.clone())
+        }
+    };
+
+    let mut records = Vec::new();
+    for gateway in gateways.iter().chain(previous.iter().flatten()) {
+        for record in index.dependents(gateway) {
+            if !records.contains(&record) {
+                records.push(record);
+            }
+        }
+    }
+    records
+}
+
+/// Resolves the content a [`GatewaySelector`] refers to. Gateways that only provide a hostname address are published
+/// as CNAME, or as the addresses the hostname resolves to if [`GatewaySelector::flatten_hostname`] is set.
+pub async fn lookup_gateway(
+    client: &kube::Client,
+    selector: &GatewaySelector,
+    ns: &str,
+    record_type: Option<RecordType>,
+    all_ips: bool,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let ns = selector.namespace.as_deref().unwrap_or(ns);
+    let name = selector.name.as_str();
+    let all_ips = all_ips || selector.all_ips.unwrap_or(false);
+
+    let gateway = kube::api::Api::<Gateway>::namespaced(client.clone(), ns)
+        .get(name)
+        .await?;
+    let Some(addresses) = gateway_addresses(&gateway) else {
+        error!("no address found for gateway {ns}/{name}");
+        return Ok(None);
+    };
+
+    let flatten_hostname = selector.flatten_hostname.unwrap_or(false);
+    resolve_addresses(addresses, record_type, all_ips, flatten_hostname, name, ns).await
+}
+
+/// The hostnames of all [`HTTPRoute`]s attached to the gateway referenced by `selector` that its listeners accept
+/// (see `allowedRoutes`), within `zone`. Wildcard hostnames are skipped. Routes are read from the store of the
+/// HTTPRoute watcher, which requires the controller to run with Gateway API support.
+pub async fn route_hostnames(ctx: &Context, selector: &GatewaySelector, ns: &str, zone: &str) -> Result<Vec<String>> {
+    let Some(routes) = ctx.routes.as_ref() else {
+        bail!("hostnames_from_routes requires the controller to run with Gateway API support (--gateway-api)");
+    };
+    routes.wait_until_ready().await?;
+
+    let ns = selector.namespace.as_deref().unwrap_or(ns);
+    let gateway = kube::api::Api::<Gateway>::namespaced(ctx.client.clone(), ns)
+        .get(&selector.name)
+        .await?;
+    let gateway_ref = Dependency::Gateway {
+        name: selector.name.clone(),
+        namespace: ns.to_string(),
+    };
+    let selects_namespaces = gateway.spec.listeners.iter().any(Listener::selects_namespaces_by_label);
+
+    let mut namespace_labels = HashMap::new();
+    let mut hostnames = Vec::new();
+    for route in routes.state() {
+        if !route.parent_gateways().contains(&gateway_ref) {
+            continue;
+        }
+        let route_ns = route.namespace().unwrap_or_else(|| "default".to_string());
+        if selects_namespaces && !namespace_labels.contains_key(&route_ns) {
+            let labels = kube::api::Api::<Namespace>::all(ctx.client.clone())
+                .get(&route_ns)
+                .await?
+                .metadata
+                .labels
+                .unwrap_or_default();
+            namespace_labels.insert(route_ns.clone(), labels);
+        }
+        let labels = namespace_labels.get(&route_ns).cloned().unwrap_or_default();
+        if !route.is_allowed_by(&gateway, &labels) {
+            debug!(
+                "HTTPRoute {route_ns}/{} is not allowed by gateway {ns}/{}",
+                route.name_any(),
+                selector.name
+            );
+            continue;
+        }
+
+        for hostname in route.spec.hostnames.iter().flatten() {
+            if !is_publishable(hostname, zone) {
+                warn!(
+                    "Skipping hostname {hostname:?} of HTTPRoute {route_ns}/{}: wildcard or not in zone {zone:?}",
+                    route.name_any()
+                );
+                continue;
+            }
+            if !hostnames.contains(hostname) {
+                hostnames.push(hostname.clone());
+            }
+        }
+    }
+    hostnames.sort();
+    Ok(hostnames)
+}
+
+/// Whether a route hostname can be published in `zone`: it must not be a wildcard and lie within the zone.
+fn is_publishable(hostname: &str, zone: &str) -> bool {
+    let hostname = hostname.trim_end_matches('.').to_ascii_lowercase();
+    let zone = zone.trim_end_matches('.').to_ascii_lowercase();
+    !hostname.contains('*') && (hostname == zone || hostname.ends_with(&format!(".{zone}")))
+}
+
+/// Extracts the addresses from the status of a [`Gateway`] resource.
+fn gateway_addresses(gateway: &Gateway) -> Option<ServiceAddresses> {
+    let addresses = gateway
+        .status
+        .as_ref()
+        .and_then(|status| status.addresses.as_ref())
+        .filter(|addresses| !addresses.is_empty())?;
+
+    let mut result = ServiceAddresses::default();
+    for address in addresses {
+        match address.type_.as_deref().unwrap_or("IPAddress") {
+            "IPAddress" => match address.value.parse::<IpAddr>() {
+                Ok(ip) => result.ips.push(ip),
+                Err(_) => warn!("Ignoring invalid gateway ip address {:?}", address.value),
+            },
+            "Hostname" => result.hostnames.push(address.value.clone()),
+            other => debug!("Ignoring gateway address of type {other:?}"),
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gateway(name: &str, namespace: &str) -> Dependency {
+        Dependency::Gateway {
+            name: name.to_string(),
+            namespace: namespace.to_string(),
+        }
+    }
+
+    #[test]
+    fn route_parents_default_to_gateways_in_the_route_namespace() {
+        let mut route = HTTPRoute::new(
+            "web",
+            HTTPRouteSpec {
+                parent_refs: Some(vec![
+                    ParentReference {
+                        name: "public".to_string(),
+                        ..Default::default()
+                    },
+                    ParentReference {
+                        name: "internal".to_string(),
+                        namespace: Some("infra".to_string()),
+                        group: Some(GATEWAY_API_GROUP.to_string()),
+                        kind: Some("Gateway".to_string()),
+                        section_name: None,
+                    },
+                    ParentReference {
+                        name: "mesh".to_string(),
+                        group: Some("".to_string()),
+                        kind: Some("Service".to_string()),
+                        ..Default::default()
+                    },
+                ]),
+                hostnames: None,
+            },
+        );
+        route.metadata.namespace = Some("apps".to_string());
+
+        assert_eq!(
+            route.parent_gateways(),
+            [gateway("public", "apps"), gateway("internal", "infra")]
+        );
+    }
+
+    #[test]
+    fn listeners_only_accept_allowed_routes() {
+        let mut gw = Gateway::new(
+            "public",
+            GatewaySpec {
+                listeners: vec![
+                    Listener {
+                        name: "http".to_string(),
+                        allowed_routes: None,
+                    },
+                    Listener {
+                        name: "https".to_string(),
+                        allowed_routes: Some(AllowedRoutes {
+                            namespaces: Some(RouteNamespaces {
+                                from: Some("Selector".to_string()),
+                                selector: Some(LabelSelector {
+                                    match_labels: Some(BTreeMap::from([("team".to_string(), "web".to_string())])),
+                                    ..Default::default()
+                                }),
+                            }),
+                            kinds: None,
+                        }),
+                    },
+                ],
+                ..Default::default()
+            },
+        );
+        gw.metadata.namespace = Some("gateway".to_string());
+
+        let route = |ns: &str, section_name: Option<&str>| {
+            let mut route = HTTPRoute::new(
+                "web",
+                HTTPRouteSpec {
+                    parent_refs: Some(vec![ParentReference {
+                        name: "public".to_string(),
+                        namespace: Some("gateway".to_string()),
+                        section_name: section_name.map(ToString::to_string),
+                        ..Default::default()
+                    }]),
+                    hostnames: None,
+                },
+            );
+            route.metadata.namespace = Some(ns.to_string());
+            route
+        };
+        let web = BTreeMap::from([("team".to_string(), "web".to_string())]);
+        let none = BTreeMap::new();
+
+        // The http listener only accepts routes of the gateway namespace.
+        assert!(route("gateway", Some("http")).is_allowed_by(&gw, &none));
+        assert!(!route("apps", Some("http")).is_allowed_by(&gw, &web));
+        // The https listener accepts routes of namespaces with matching labels.
+        assert!(route("apps", Some("https")).is_allowed_by(&gw, &web));
+        assert!(!route("apps", Some("https")).is_allowed_by(&gw, &none));
+        assert!(route("apps", None).is_allowed_by(&gw, &web));
+        assert!(!route("apps", None).is_allowed_by(&gw, &none));
+    }
+
+    #[test]
+    fn only_hostnames_within_the_zone_are_published() {
+        assert!(is_publishable("example.com", "example.com"));
+        assert!(is_publishable("Shop.Example.com.", "example.com"));
+        assert!(!is_publishable("*.example.com", "example.com"));
+        assert!(!is_publishable("example.org", "example.com"));
+        assert!(!is_publishable("badexample.com", "example.com"));
+    }
+
+    #[test]
+    fn gateway_addresses_by_type() {
+        let mut gw = Gateway::new("public", GatewaySpec::default());
+        gw.status = Some(GatewayStatus {
+            addresses: Some(vec![
+                GatewayStatusAddress {
+                    type_: None,
+                    value: "1.2.3.4".to_string(),
+                },
+                GatewayStatusAddress {
+                    type_: Some("Hostname".to_string()),
+                    value: "lb.example.com".to_string(),
+                },
+                GatewayStatusAddress {
+                    type_: Some("example.com/custom".to_string()),
+                    value: "foo".to_string(),
+                },
+            ]),
+        });
+
+        let addresses = gateway_addresses(&gw).unwrap();
+        assert_eq!(addresses.ips, ["1.2.3.4".parse::<IpAddr>().unwrap()]);
+        assert_eq!(addresses.hostnames, ["lb.example.com"]);
+    }
+}
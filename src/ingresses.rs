@@ -0,0 +1,76 @@
+use crate::{
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    resources::{
+        CloudflareDNSRecord,
+        IngressSelector,
+        RecordType,
+        ResolvedContent,
+    },
+    services::{
+        resolve_addresses,
+        ServiceAddresses,
+    },
+};
+use eyre::Result;
+use k8s_openapi::api::networking::v1::Ingress;
+use kube::runtime::reflector::ObjectRef;
+use std::net::IpAddr;
+
+/// Maps a change of an [`Ingress`] to all `CloudflareDNSRecord`s referencing it.
+pub fn records_referencing_ingress(index: &DependencyIndex, ingress: Ingress) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (ingress.metadata.name, ingress.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::Ingress { name, namespace })
+}
+
+/// Resolves the content an [`IngressSelector`] refers to. Ingresses whose load balancer only provides a hostname are
+/// published as CNAME, or as the addresses the hostname resolves to if [`IngressSelector::flatten_hostname`] is set.
+pub async fn lookup_ingress(
+    client: &kube::Client,
+    selector: &IngressSelector,
+    ns: &str,
+    record_type: Option<RecordType>,
+    all_ips: bool,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let ns = selector.namespace.as_deref().unwrap_or(ns);
+    let name = selector.name.as_str();
+    let all_ips = all_ips || selector.all_ips.unwrap_or(false);
+
+    let Some(addresses) = ingress_addresses(client, name, ns).await? else {
+        error!("no load balancer address found for ingress {ns}/{name}");
+        return Ok(None);
+    };
+
+    let flatten_hostname = selector.flatten_hostname.unwrap_or(false);
+    resolve_addresses(addresses, record_type, all_ips, flatten_hostname, name, ns).await
+}
+
+/// Extracts the load balancer addresses from the status of an [`Ingress`] resource.
+pub async fn ingress_addresses(client: &kube::Client, name: &str, ns: &str) -> Result<Option<ServiceAddresses>> {
+    let ingress = kube::api::Api::<Ingress>::namespaced(client.clone(), ns)
+        .get(name)
+        .await?;
+
+    let Some(lb_ingress) = ingress
+        .status
+        .as_ref()
+        .and_then(|s| s.load_balancer.as_ref())
+        .and_then(|lb| lb.ingress.as_ref())
+        .filter(|ingress| !ingress.is_empty())
+    else {
+        warn!("Ingress {ns}/{name} has no load balancer status yet");
+        return Ok(None);
+    };
+
+    Ok(Some(ServiceAddresses {
+        ips: lb_ingress
+            .iter()
+            .filter_map(|i| i.ip.as_deref()?.parse::<IpAddr>().ok())
+            .collect(),
+        hostnames: lb_ingress.iter().filter_map(|i| i.hostname.clone()).collect(),
+    }))
+}
@@ -19,6 +19,11 @@
 //! will be reflected in the `status.pending` field of the `CloudflareDNSRecord` resource. For this to be enabled, set
 //! the env var `CHECK_DNS_RESOLUTION` to a human readable duration like `5m` or `1h` or `60s`.
 //!
+//! Requests to the cloudflare API can be routed through a proxy with `CLOUDFLARE_PROXY` (otherwise `HTTPS_PROXY` is
+//! honored), additional CA certificates, e.g. of a TLS intercepting proxy, can be trusted with `CLOUDFLARE_CA_CERT`
+//! (path to a PEM file) and the API endpoint can be changed with `CLOUDFLARE_API_URL`. Requests time out after
+//! `CLOUDFLARE_TIMEOUT` (default `30s`), connecting after `CLOUDFLARE_CONNECT_TIMEOUT` (default `10s`).
+//!
 //! You can then create a new DNS record like this:
 //!
 //! ```yaml
@@ -52,6 +57,177 @@
 //! # ...
 //! ```
 //!
+//! By default a single IP of the service is published. Set `all_ips: true` on the service reference to publish every
+//! load balancer ingress IP of the matching address family (ipv4 for `A`, ipv6 for `AAAA` records) as a separate
+//! record. The records are kept in sync as ingress IPs come and go.
+//!
+//! Load balancers that provide a hostname instead of an IP (e.g. AWS ELBs) are published as a `CNAME` record pointing
+//! to that hostname. Set `flatten_hostname: true` on the service reference to resolve the hostname and publish its
+//! addresses as `A`/`AAAA` records instead.
+//!
+//! Ingresses can be referenced the same way. The operator publishes the load balancer address from the ingress status
+//! and updates the record when it changes. `all_ips` and `flatten_hostname` work as for services:
+//!
+//! ```yaml
+//! # ...
+//!   content:
+//!     ingress:
+//!       name: my-app
+//!       namespace: apps
+//! # ...
+//! ```
+//!
+//! With [Gateway API](https://gateway-api.sigs.k8s.io/), reference a `Gateway` to publish the addresses from its
+//! status. This requires running the controller with `GATEWAY_API=true` (or `--gateway-api`). Set
+//! `hostnames_from_routes: true` to additionally publish the record under every hostname of the `HTTPRoute`s attached
+//! to the gateway. Only routes the gateway listeners accept (see `allowedRoutes`, by default routes of the gateway
+//! namespace) are considered, and only hostnames within the zone of the record. Wildcard hostnames are skipped:
+//!
+//! ```yaml
+//! # ...
+//!   content:
+//!     gateway:
+//!       name: public
+//!       namespace: gateway
+//!       hostnames_from_routes: true
+//! # ...
+//! ```
+//!
+//! For workloads using `hostNetwork` or NodePort services, reference nodes by label selector. Every ready node matching
+//! the selector contributes its `ExternalIP` addresses (or its `InternalIP` addresses if it has no external ones). Set
+//! `all_ips: false` to publish only a single address. The records follow nodes joining, leaving or changing readiness:
+//!
+//! ```yaml
+//! # ...
+//!   content:
+//!     node:
+//!       selector: node-role.kubernetes.io/edge=true
+//! # ...
+//! ```
+//!
+//! Clusters behind NAT with a dynamic address (e.g. home labs) can publish their public IP. The controller discovers it
+//! via a DNS query (by default the `whoami.cloudflare` TXT record at 1.1.1.1) or an HTTP echo endpoint configured with
+//! `PUBLIC_IP_SOURCE` (e.g. `https://api.ipify.org`), re-checks it every `PUBLIC_IP_CHECK_INTERVAL` (default `5m`) and
+//! updates all records using it when it changes:
+//!
+//! ```yaml
+//! # ...
+//!   content:
+//!     publicIp: {}
+//! # ...
+//! ```
+//!
+//! For contents combining several sources, such as SPF or DKIM TXT records, use a `template`. Placeholders can refer to
+//! `values` (set directly or read from a `configMap` or `secret`) or to an address of a service with
+//! `service.[<namespace>.]<name>.<ip|ipv4|ipv6|hostname>`. The record is updated when any referenced object changes:
+//!
+//! ```yaml
+//! # ...
+//!   type: TXT
+//!   content:
+//!     template:
+//!       template: "v=spf1 ip4:{{ service.traefik.traefik.ipv4 }} include:{{ values.provider }} -all"
+//!       values:
+//!         provider:
+//!           from:
+//!             configMap:
+//!               name: mail-settings
+//!               key: spf-include
+//! # ...
+//! ```
+//!
+//! Contents such as DKIM keys or verification tokens can be read from a `secret` or `configMap`. Changes to the
+//! referenced object are picked up right away:
+//!
+//! ```yaml
+//! # ...
+//!   type: TXT
+//!   content:
+//!     from:
+//!       secret:
+//!         name: dkim
+//!         key: txt-record
+//! # ...
+//! ```
+//!
+//! For dual-stack services, set `dual_stack: true` instead of a `type`. The operator then publishes an `A` record for
+//! every ipv4 and an `AAAA` record for every ipv6 address of the service. The created records of both families are
+//! listed in `status.records`.
+//!
+//! To publish several records under the same name (e.g. round-robin A records or multiple MX entries), use `contents`
+//! instead of (or in addition to) `content`. The operator makes sure exactly one cloudflare record exists per entry:
+//!
+//! ```yaml
+//! # ...
+//!   contents:
+//!     - value: "1.2.3.4"
+//!     - value: "5.6.7.8"
+//!     - service:
+//!         name: traefik
+//!         namespace: traefik
+//! # ...
+//! ```
+//!
+//! Records with several fields (MX, SRV, CAA, HTTPS, SVCB, TLSA, DS, SSHFP, NAPTR, CERT, URI and LOC) take their data
+//! as structured content named after the type (e.g. `mx`, `caa`, `loc`), or as a value in zone file format such as
+//! `10 mail.example.com`:
+//!
+//! ```yaml
+//! # ...
+//!   type: MX
+//!   contents:
+//!     - mx:
+//!         priority: 10
+//!         exchange: mail.example.com
+//!     - value: "20 backup-mail.example.com"
+//! # ...
+//! ---
+//! # ...
+//!   name: _sip._tcp.example.com
+//!   type: SRV
+//!   content:
+//!     srv:
+//!       priority: 10
+//!       weight: 5
+//!       port: 5060
+//!       target: sip.example.com
+//! # ...
+//! ---
+//! # ...
+//!   type: CAA
+//!   contents:
+//!     - caa:
+//!         flags: 0
+//!         tag: issue
+//!         value: letsencrypt.org
+//!     - value: '0 iodef "mailto:security@example.com"'
+//! # ...
+//! ---
+//! # ...
+//!   type: HTTPS
+//!   content:
+//!     https:
+//!       priority: 1
+//!       target: .
+//!       value: alpn="h3,h2"
+//! # ...
+//! ---
+//! # ...
+//!   type: LOC
+//!   content:
+//!     loc:
+//!       lat_degrees: 52
+//!       lat_minutes: 31
+//!       lat_seconds: 12.5
+//!       lat_direction: N
+//!       long_degrees: 13
+//!       long_minutes: 24
+//!       long_seconds: 36
+//!       long_direction: E
+//!       altitude: 34
+//! # ...
+//! ```
+//!
 //! The zone can also be set with a `secret` or `configMap` reference like this:
 //!
 //! ```yaml
@@ -65,6 +241,47 @@
 //! # ...
 //! ```
 //!
+//! Records created by the operator are marked as owned by their `CloudflareDNSRecord` via a marker in the record
+//! comment. Comments are cut off to fit cloudflare's limit of 100 characters together with the marker. Existing records
+//! with the same name and type that carry no such marker (e.g. created manually) are adopted by default: they are
+//! updated to match the spec, but never deleted. Set `ownership` to `takeover` to also take over records owned by other
+//! resources and to delete superfluous records the resource did not create, or to `refuse` to only ever modify records
+//! the resource created itself. Records of other types (such as TXT verification records sharing the hostname) are only
+//! deleted if the resource created them, e.g. after its `type` changed.
+//!
+//! Records can be managed with the API token of another cloudflare account, e.g. one per team, instead of the token of
+//! the operator. Create `CloudflareCredentials` reading the token from a secret in the namespace of the records,
+//! optionally restricted to the zones of one `account_id` and to `allowed_zones` (names or IDs), and reference them
+//! with `credentials`. Each set of credentials gets its own API client and caches, requests with the same token share
+//! its rate limit:
+//!
+//! ```yaml
+//! apiVersion: dns.cloudflare.com/v1alpha1
+//! kind: CloudflareCredentials
+//! metadata:
+//!   name: team-a
+//! spec:
+//!   api_token:
+//!     name: team-a-cloudflare
+//!     key: api-token
+//!   allowed_zones:
+//!     - team-a.example.com
+//! ---
+//! apiVersion: dns.cloudflare.com/v1alpha1
+//! kind: CloudflareDNSRecord
+//! metadata:
+//!   name: my-team-record
+//! spec:
+//!   name: app.team-a.example.com
+//!   credentials: team-a
+//! # ...
+//! ```
+//!
+//! Records are updated when their credentials or the secret holding the token change. Records are always deleted with
+//! the credentials they were created with, so changing `credentials` moves the records to the new account. Keep
+//! credentials until the records managed with them are deleted: if the credentials or their secret are gone, the
+//! records are left behind with a warning event.
+//!
 //! See [CloudflareDNSRecordSpec](https://docs.rs/cloudflare-dns-operator/latest/cloudflare_dns_operator/resources/struct.CloudflareDNSRecordSpec.html) for more details.
 
 #[macro_use]
@@ -72,8 +289,16 @@ extern crate tracing;
 
 pub mod conditions;
 pub mod context;
+pub mod credentials;
+pub mod dependencies;
 pub mod dns;
 pub mod dns_check;
+pub mod gateways;
+pub mod ingresses;
+pub mod nodes;
+pub mod public_ip;
 pub mod reconcile;
+pub mod record_data;
 pub mod resources;
 pub mod services;
+pub mod templates;
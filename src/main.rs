@@ -4,8 +4,33 @@ extern crate tracing;
 use clap::Parser;
 use cloudflare_dns_operator::{
     context,
-    dns::cloudflare::CloudflareApi,
+    credentials::{
+        self,
+        CredentialsClients,
+    },
+    dependencies::{
+        self,
+        DependencyIndex,
+    },
+    dns::cloudflare::{
+        self,
+        CloudflareApi,
+        HttpOptions,
+    },
     dns_check,
+    gateways::{
+        self,
+        Gateway,
+        HTTPRoute,
+        RouteParents,
+    },
+    ingresses,
+    nodes,
+    public_ip::{
+        self,
+        PublicIp,
+        PublicIpSource,
+    },
     reconcile::{
         self,
         ReconcileError,
@@ -15,23 +40,41 @@ use cloudflare_dns_operator::{
 };
 use context::Context;
 use eyre::Result;
-use futures::StreamExt as _;
-use k8s_openapi::api::core::v1::Service;
+use futures::{
+    StreamExt as _,
+    TryStreamExt as _,
+};
+use k8s_openapi::api::{
+    core::v1::{
+        ConfigMap,
+        Node,
+        Secret,
+        Service,
+    },
+    networking::v1::Ingress,
+};
 use kube::{
     runtime::{
         controller::Action,
+        events::{
+            Recorder,
+            Reporter,
+        },
         finalizer,
         finalizer::Event,
+        metadata_watcher,
+        reflector,
         watcher,
         Controller,
+        WatchStreamExt as _,
     },
     Api,
     CustomResourceExt as _,
     Resource as _,
 };
-use services::is_suitable_service;
 use std::{
     net::SocketAddr,
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
@@ -50,6 +93,47 @@ struct ArgsController {
     #[clap(long, env = "CLOUDFLARE_API_TOKEN", help = "Cloudflare API token")]
     cloudflare_api_token: String,
 
+    #[clap(
+        long,
+        env = "CLOUDFLARE_API_URL",
+        help = "Base URL of the cloudflare API, e.g. of a staging environment or a forwarding proxy",
+        default_value = cloudflare::DEFAULT_API_URL
+    )]
+    cloudflare_api_url: String,
+
+    #[clap(
+        long,
+        env = "CLOUDFLARE_PROXY",
+        help = "Proxy for cloudflare API requests, e.g. http://proxy.internal:3128. If not set, HTTPS_PROXY is honored"
+    )]
+    cloudflare_proxy: Option<String>,
+
+    #[clap(
+        long,
+        env = "CLOUDFLARE_CA_CERT",
+        help = "PEM file with additional CA certificates to trust for cloudflare API requests, e.g. of a TLS \
+                intercepting proxy"
+    )]
+    cloudflare_ca_cert: Option<PathBuf>,
+
+    #[clap(
+        long,
+        env = "CLOUDFLARE_TIMEOUT",
+        help = "Timeout of cloudflare API requests",
+        default_value = "30s",
+        value_parser = humantime::parse_duration
+    )]
+    cloudflare_timeout: Duration,
+
+    #[clap(
+        long,
+        env = "CLOUDFLARE_CONNECT_TIMEOUT",
+        help = "Timeout for connecting to the cloudflare API",
+        default_value = "10s",
+        value_parser = humantime::parse_duration
+    )]
+    cloudflare_connect_timeout: Duration,
+
     #[clap(
         long = "dns-check",
         env = "CHECK_DNS_RESOLUTION",
@@ -65,6 +149,31 @@ struct ArgsController {
         default_value = "1.1.1.1:53"
     )]
     nameserver: SocketAddr,
+
+    #[clap(
+        long,
+        env = "GATEWAY_API",
+        help = "Watch Gateway API resources (Gateways and HTTPRoutes). Requires the Gateway API CRDs to be installed"
+    )]
+    gateway_api: bool,
+
+    #[clap(
+        long,
+        env = "PUBLIC_IP_SOURCE",
+        help = "Where to discover the public ip for publicIp contents: an http(s) url responding with the ip, or a \
+                dns://<nameserver>/<name> (dns+ch:// for the CH class) TXT record",
+        default_value = "dns+ch://1.1.1.1/whoami.cloudflare"
+    )]
+    public_ip_source: PublicIpSource,
+
+    #[clap(
+        long,
+        env = "PUBLIC_IP_CHECK_INTERVAL",
+        help = "How often to re-check the public ip",
+        default_value = "5m",
+        value_parser = humantime::parse_duration
+    )]
+    public_ip_interval: Duration,
 }
 
 #[tokio::main]
@@ -74,15 +183,26 @@ async fn main() -> Result<()> {
 
     match Args::parse() {
         Args::Crds => {
-            let yaml = serde_yaml::to_string(&resources::CloudflareDNSRecord::crd()).expect("Failed to serialize CRD");
-            print!("{yaml}")
+            for (i, crd) in [
+                resources::CloudflareDNSRecord::crd(),
+                resources::CloudflareCredentials::crd(),
+            ]
+            .iter()
+            .enumerate()
+            {
+                let yaml = serde_yaml::to_string(crd).expect("Failed to serialize CRD");
+                if i > 0 {
+                    println!("---");
+                }
+                print!("{yaml}")
+            }
         }
         Args::Controller(args) => {
             run_controller(args).await?;
         }
         Args::ListZones(args) => {
-            let cloudflare_api = CloudflareApi::new(args.cloudflare_api_token);
-            let zones = cloudflare_api.list_zones().await?;
+            let cloudflare_api = args.cloudflare_api()?;
+            let zones = cloudflare_api.list_zone_infos().await?;
             dbg!(zones);
         }
     }
@@ -90,41 +210,165 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_controller(
-    ArgsController {
-        cloudflare_api_token,
+impl ArgsController {
+    fn http_client(&self) -> Result<reqwest::Client> {
+        HttpOptions {
+            proxy: self.cloudflare_proxy.clone(),
+            ca_certificates: self.cloudflare_ca_cert.clone(),
+            timeout: self.cloudflare_timeout,
+            connect_timeout: self.cloudflare_connect_timeout,
+        }
+        .client()
+    }
+
+    fn cloudflare_api(&self) -> Result<CloudflareApi> {
+        Ok(CloudflareApi::new(self.cloudflare_api_token.clone())
+            .with_api_url(&self.cloudflare_api_url)
+            .with_http_client(self.http_client()?))
+    }
+}
+
+async fn run_controller(args: ArgsController) -> Result<(), ReconcileError> {
+    let credentials = CredentialsClients::new(&args.cloudflare_api_url, args.http_client()?);
+    // Credentials using the token of the operator share its rate limit.
+    let dns_provider = Arc::new(
+        args.cloudflare_api()?
+            .with_rate_limiter(credentials.rate_limiter(&args.cloudflare_api_token)),
+    );
+    let ArgsController {
         dns_checks,
         nameserver,
-    }: ArgsController,
-) -> Result<(), ReconcileError> {
+        gateway_api,
+        public_ip_source,
+        public_ip_interval,
+        ..
+    } = args;
+
     let client = kube::Client::try_default().await?;
 
+    // HTTPRoutes are kept in a store, route hostnames are looked up from it on every reconcile.
+    let (routes, route_writer) = reflector::store();
+    // CloudflareCredentials are kept in a store to find those reading their API token from a changed secret.
+    let (credentials_store, credentials_writer) = reflector::store();
+
     let dns_resources = Api::<resources::CloudflareDNSRecord>::all(client.clone());
 
     let (dns_check_tx, dns_check_rx) = mpsc::channel(64);
 
-    let cloudflare_api = CloudflareApi::new(cloudflare_api_token);
+    let reporter = Reporter {
+        controller: "cloudflare-dns-operator".to_string(),
+        instance: std::env::var("POD_NAME").ok(),
+    };
 
     let context = Arc::new(Context {
         client: client.clone(),
-        cloudflare_api,
+        dns_provider,
+        credentials,
+        recorder: Recorder::new(client.clone(), reporter),
         do_dns_check: dns_checks.is_some(),
         dns_check_tx,
         dns_lookup_success: Default::default(),
+        public_ip: PublicIp::new(public_ip_source),
+        routes: gateway_api.then_some(routes),
     });
 
     let dns_change = dns_check::start_dns_check(context.clone(), dns_check_rx, dns_checks, nameserver);
 
     info!("Starting controller");
 
-    Controller::new(dns_resources, Default::default())
+    // Keep track of the resources CloudflareDNSRecords depend on, so changes to them requeue every record referencing
+    // them.
+    let index = DependencyIndex::default();
+    let public_ip_change =
+        public_ip::start_public_ip_check(context.public_ip.clone(), index.clone(), public_ip_interval);
+    let (reader, writer) = reflector::store();
+    let dns_resources = watcher(dns_resources, watcher::Config::default())
+        .default_backoff()
+        .reflect(writer)
+        .inspect_ok({
+            let index = index.clone();
+            move |event| index.apply_watcher_event(event)
+        })
+        .applied_objects();
+
+    let mut controller = Controller::for_stream(dns_resources, reader)
         // watch load balancers / external ip services to adjust dns <-> public ip
-        .watches(
-            Api::<Service>::all(client),
-            watcher::Config::default(),
-            is_suitable_service,
+        .watches(Api::<Service>::all(client.clone()), watcher::Config::default(), {
+            let index = index.clone();
+            move |svc| services::records_referencing_service(&index, svc)
+        })
+        // watch ingresses to adjust dns <-> ingress load balancer address
+        .watches(Api::<Ingress>::all(client.clone()), watcher::Config::default(), {
+            let index = index.clone();
+            move |ingress| ingresses::records_referencing_ingress(&index, ingress)
+        })
+        // watch nodes to adjust dns <-> node addresses as nodes come, go or change readiness
+        .watches_stream(nodes::node_changes(Api::<Node>::all(client.clone())), {
+            let index = index.clone();
+            move |node| nodes::records_referencing_nodes(&index, node)
+        })
+        // watch credentials records are managed with
+        .watches_stream(
+            watcher(
+                Api::<resources::CloudflareCredentials>::all(client.clone()),
+                watcher::Config::default(),
+            )
+            .default_backoff()
+            .reflect(credentials_writer)
+            .touched_objects(),
+            {
+                let index = index.clone();
+                move |credentials| credentials::records_using_credentials(&index, credentials)
+            },
         )
+        // watch configmaps and secrets referenced by templates, zones or credentials
+        .watches_stream(
+            metadata_watcher(Api::<ConfigMap>::all(client.clone()), watcher::Config::default())
+                .default_backoff()
+                .touched_objects(),
+            {
+                let index = index.clone();
+                move |config_map| dependencies::records_referencing_config_map(&index, config_map)
+            },
+        )
+        .watches_stream(
+            metadata_watcher(Api::<Secret>::all(client.clone()), watcher::Config::default())
+                .default_backoff()
+                .touched_objects(),
+            {
+                let index = index.clone();
+                move |secret| {
+                    let mut records = credentials::records_using_token_secret(&index, &credentials_store, &secret);
+                    records.extend(dependencies::records_referencing_secret(&index, secret));
+                    records
+                }
+            },
+        );
+
+    if gateway_api {
+        let parents = RouteParents::default();
+        controller = controller
+            // watch gateways to adjust dns <-> gateway address
+            .watches(Api::<Gateway>::all(client.clone()), watcher::Config::default(), {
+                let index = index.clone();
+                move |gateway| gateways::records_referencing_gateway(&index, gateway)
+            })
+            // watch routes to publish records under their hostnames
+            .watches_stream(
+                watcher(Api::<HTTPRoute>::all(client), watcher::Config::default())
+                    .default_backoff()
+                    .reflect(route_writer)
+                    .touched_objects(),
+                {
+                    let index = index.clone();
+                    move |route| gateways::records_referencing_route(&index, &parents, route)
+                },
+            );
+    }
+
+    controller
         .reconcile_on(dns_change)
+        .reconcile_on(public_ip_change)
         .shutdown_on_signal()
         .run(reconcile, error_policy, context)
         .for_each(|msg| async move { info!("Reconciled: {:?}", msg) })
@@ -177,13 +421,10 @@ fn error_policy(
 ) -> Action {
     error!("Error reconciling: {:?}", err);
 
-    let is_rate_limit = match err {
-        finalizer::Error::CleanupFailed(ReconcileError::Other(report))
-        | finalizer::Error::ApplyFailed(ReconcileError::Other(report)) => {
-            report.to_string().contains("cloudflare api error") && report.to_string().contains("429")
-        }
-        _ => false,
+    let requeue_after = match err {
+        finalizer::Error::CleanupFailed(err) | finalizer::Error::ApplyFailed(err) => err.requeue_after(),
+        _ => Duration::from_secs(60),
     };
 
-    Action::requeue(Duration::from_secs(if is_rate_limit { 5 * 60 } else { 60 }))
+    Action::requeue(requeue_after)
 }
@@ -0,0 +1,214 @@
+use crate::{
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    resources::{
+        CloudflareDNSRecord,
+        NodeSelector,
+        RecordType,
+        ResolvedContent,
+    },
+    services::filter_ips,
+};
+use eyre::Result;
+use futures::{
+    Stream,
+    TryStreamExt as _,
+};
+use k8s_openapi::api::core::v1::Node;
+use kube::{
+    api::ListParams,
+    runtime::{
+        reflector::ObjectRef,
+        watcher,
+        WatchStreamExt as _,
+    },
+    Api,
+    ResourceExt as _,
+};
+use std::{
+    collections::{
+        hash_map::DefaultHasher,
+        HashMap,
+    },
+    hash::{
+        Hash as _,
+        Hasher as _,
+    },
+    net::IpAddr,
+};
+
+/// Maps a change of a [`Node`] to all `CloudflareDNSRecord`s with node content. Node selectors are label based, so
+/// every such record is affected.
+pub fn records_referencing_nodes(index: &DependencyIndex, _node: Node) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    index.dependents(&Dependency::Nodes)
+}
+
+/// Watches nodes, only yielding nodes that were added, removed or whose labels, addresses or readiness changed. Nodes
+/// report their status regularly, reconciling on each of these updates would be wasteful.
+pub fn node_changes(api: Api<Node>) -> impl Stream<Item = Result<Node, watcher::Error>> {
+    let mut seen = HashMap::<String, u64>::new();
+    watcher(api, watcher::Config::default())
+        .default_backoff()
+        .try_filter_map(move |event| {
+            let changed = match event {
+                watcher::Event::Apply(node) | watcher::Event::InitApply(node) => {
+                    let hash = relevant_state(&node);
+                    (seen.insert(node.name_any(), hash) != Some(hash)).then_some(node)
+                }
+                watcher::Event::Delete(node) => {
+                    seen.remove(&node.name_any());
+                    Some(node)
+                }
+                watcher::Event::Init | watcher::Event::InitDone => None,
+            };
+            futures::future::ready(Ok(changed))
+        })
+}
+
+/// Hash of the parts of a [`Node`] that influence the published addresses.
+fn relevant_state(node: &Node) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    node.labels().hash(&mut hasher);
+    is_ready(node).hash(&mut hasher);
+    node_ips(node).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Resolves the content a [`NodeSelector`] refers to: the addresses of all ready nodes matching the label selector, or
+/// only the first one unless `all_ips` or [`NodeSelector::all_ips`] is set.
+pub async fn lookup_nodes(
+    client: &kube::Client,
+    selector: &NodeSelector,
+    record_type: Option<RecordType>,
+    all_ips: bool,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let all_ips = all_ips || selector.all_ips.unwrap_or(true);
+    let mut params = ListParams::default();
+    if let Some(labels) = selector.selector.as_deref() {
+        params = params.labels(labels);
+    }
+    let nodes = Api::<Node>::all(client.clone()).list(&params).await?;
+
+    let mut ips = Vec::new();
+    for node in nodes.iter().filter(|node| is_ready(node)) {
+        for ip in node_ips(node) {
+            if !ips.contains(&ip) {
+                ips.push(ip);
+            }
+        }
+    }
+
+    let mut ips = filter_ips(ips, record_type);
+    if !all_ips {
+        ips.truncate(1);
+    }
+    if ips.is_empty() {
+        error!(
+            "no ready node with a matching address found for selector {:?}",
+            selector.selector
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(
+        ips.into_iter()
+            .map(|ip| ResolvedContent::Value(ip.to_string()))
+            .collect(),
+    ))
+}
+
+fn is_ready(node: &Node) -> bool {
+    node.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| conditions.iter().find(|condition| condition.type_ == "Ready"))
+        .is_some_and(|condition| condition.status == "True")
+}
+
+/// The `ExternalIP` addresses of a node, or its `InternalIP` addresses if it has no external ones.
+fn node_ips(node: &Node) -> Vec<IpAddr> {
+    let addresses = node
+        .status
+        .as_ref()
+        .and_then(|status| status.addresses.as_deref())
+        .unwrap_or_default();
+    let of_type = |ty: &str| {
+        addresses
+            .iter()
+            .filter(|address| address.type_ == ty)
+            .filter_map(|address| address.address.parse::<IpAddr>().ok())
+            .collect::<Vec<_>>()
+    };
+
+    let external = of_type("ExternalIP");
+    if !external.is_empty() {
+        return external;
+    }
+    of_type("InternalIP")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use k8s_openapi::api::core::v1::{
+        NodeAddress,
+        NodeCondition,
+        NodeStatus,
+    };
+
+    fn node(addresses: &[(&str, &str)], ready: bool) -> Node {
+        Node {
+            status: Some(NodeStatus {
+                addresses: Some(
+                    addresses
+                        .iter()
+                        .map(|(ty, address)| NodeAddress {
+                            type_: ty.to_string(),
+                            address: address.to_string(),
+                        })
+                        .collect(),
+                ),
+                conditions: Some(vec![NodeCondition {
+                    type_: "Ready".to_string(),
+                    status: if ready { "True" } else { "False" }.to_string(),
+                    ..Default::default()
+                }]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn external_ips_are_preferred_over_internal_ips() {
+        let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+        let both = node(
+            &[
+                ("InternalIP", "10.0.0.1"),
+                ("ExternalIP", "1.2.3.4"),
+                ("Hostname", "edge-1"),
+            ],
+            true,
+        );
+        assert_eq!(node_ips(&both), [ip("1.2.3.4")]);
+
+        let internal = node(&[("InternalIP", "10.0.0.1"), ("InternalIP", "fd00::1")], true);
+        assert_eq!(node_ips(&internal), [ip("10.0.0.1"), ip("fd00::1")]);
+    }
+
+    #[test]
+    fn readiness_changes_the_relevant_state() {
+        let ready = node(&[("ExternalIP", "1.2.3.4")], true);
+        let not_ready = node(&[("ExternalIP", "1.2.3.4")], false);
+        assert!(is_ready(&ready));
+        assert!(!is_ready(&not_ready));
+        assert_ne!(relevant_state(&ready), relevant_state(&not_ready));
+
+        let mut heartbeat = ready.clone();
+        heartbeat.metadata.resource_version = Some("42".to_string());
+        assert_eq!(relevant_state(&ready), relevant_state(&heartbeat));
+    }
+}
@@ -0,0 +1,250 @@
+//! Discovery of the public (egress) IP of the cluster, e.g. for clusters behind NAT with a dynamic address. The IP is
+//! looked up via an HTTP echo endpoint or a DNS query and re-checked periodically, records using a `publicIp` content
+//! are reconciled when it changes.
+
+use crate::{
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    dns::{
+        cloudflare::HttpOptions,
+        lookup as dns_lookup,
+    },
+    resources::{
+        CloudflareDNSRecord,
+        RecordType,
+        ResolvedContent,
+    },
+};
+use eyre::{
+    Context as _,
+    Result,
+};
+use futures::Stream;
+use kube::runtime::reflector::ObjectRef;
+use rsdns::records::Class;
+use std::{
+    net::{
+        IpAddr,
+        SocketAddr,
+    },
+    sync::{
+        Arc,
+        RwLock,
+    },
+    time::Duration,
+};
+
+/// Where to discover the public IP.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublicIpSource {
+    /// An HTTP endpoint responding with the IP of the client as plain text, e.g. `https://api.ipify.org`.
+    Http(String),
+    /// A TXT record resolving to the IP of the client, e.g. `whoami.cloudflare` in the CH class at 1.1.1.1. Written as
+    /// `dns://1.1.1.1/whoami.cloudflare` (IN class) or `dns+ch://1.1.1.1/whoami.cloudflare` (CH class).
+    Dns {
+        nameserver: SocketAddr,
+        qname: String,
+        class: Class,
+    },
+}
+
+impl std::str::FromStr for PublicIpSource {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with("http://") || s.starts_with("https://") {
+            return Ok(PublicIpSource::Http(s.to_string()));
+        }
+
+        let (class, rest) = if let Some(rest) = s.strip_prefix("dns://") {
+            (Class::IN, rest)
+        } else if let Some(rest) = s.strip_prefix("dns+ch://") {
+            (Class::CH, rest)
+        } else {
+            eyre::bail!("Invalid public ip source {s:?}, expected an http(s):// or dns:// url");
+        };
+
+        let Some((nameserver, qname)) = rest.split_once('/').filter(|(_, qname)| !qname.is_empty()) else {
+            eyre::bail!("Invalid public ip source {s:?}, expected dns://<nameserver>/<name>");
+        };
+        let nameserver = match nameserver.parse::<SocketAddr>() {
+            Ok(it) => it,
+            Err(_) => SocketAddr::new(
+                nameserver
+                    .parse::<IpAddr>()
+                    .map_err(|_| eyre::eyre!("Invalid nameserver {nameserver:?} in public ip source {s:?}"))?,
+                53,
+            ),
+        };
+
+        Ok(PublicIpSource::Dns {
+            nameserver,
+            qname: qname.to_string(),
+            class,
+        })
+    }
+}
+
+impl std::fmt::Display for PublicIpSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PublicIpSource::Http(url) => write!(f, "{url}"),
+            PublicIpSource::Dns {
+                nameserver,
+                qname,
+                class,
+            } => {
+                let scheme = if *class == Class::CH { "dns+ch" } else { "dns" };
+                write!(f, "{scheme}://{nameserver}/{qname}")
+            }
+        }
+    }
+}
+
+/// The public IP of the cluster, discovered on first use and then kept up to date by [`start_public_ip_check`].
+#[derive(Clone)]
+pub struct PublicIp {
+    source: PublicIpSource,
+    current: Arc<RwLock<Option<IpAddr>>>,
+    http: reqwest::Client,
+}
+
+impl PublicIp {
+    /// HTTP echo endpoints are queried with the timeouts of the default [`HttpOptions`], so that a hanging endpoint
+    /// does not block reconciles.
+    pub fn new(source: PublicIpSource) -> Self {
+        Self {
+            source,
+            current: Default::default(),
+            http: HttpOptions::default().client().expect("default http client"),
+        }
+    }
+
+    /// The last discovered public IP. Discovers it if that did not happen yet.
+    pub async fn current(&self) -> Result<IpAddr> {
+        if let Some(ip) = *self.current.read().expect("public ip lock") {
+            return Ok(ip);
+        }
+        let (ip, _) = self.refresh().await?;
+        Ok(ip)
+    }
+
+    /// Discovers the public IP again. Returns the IP and whether it changed.
+    pub async fn refresh(&self) -> Result<(IpAddr, bool)> {
+        let ip = self.discover().await?;
+        let previous = self.current.write().expect("public ip lock").replace(ip);
+        Ok((ip, previous != Some(ip)))
+    }
+
+    async fn discover(&self) -> Result<IpAddr> {
+        let response = match &self.source {
+            PublicIpSource::Http(url) => {
+                self.http
+                    .get(url)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status())
+                    .with_context(|| format!("public ip request to {url} failed"))?
+                    .text()
+                    .await?
+            }
+            PublicIpSource::Dns {
+                nameserver,
+                qname,
+                class,
+            } => dns_lookup::resolve_txt(qname, *class, *nameserver)
+                .await
+                .with_context(|| format!("public ip lookup of {qname} at {nameserver} failed"))?
+                .into_iter()
+                .next()
+                .unwrap_or_default(),
+        };
+
+        let response = response.trim().trim_matches('"');
+        response
+            .parse::<IpAddr>()
+            .with_context(|| format!("{} did not respond with an ip address: {response:?}", self.source))
+    }
+}
+
+/// Resolves a `publicIp` content to the discovered public IP, if it matches the address family of `record_type`.
+pub async fn lookup_public_ip(
+    public_ip: &PublicIp,
+    record_type: Option<RecordType>,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let ip = public_ip.current().await?;
+    let matches = match record_type {
+        Some(RecordType::A) => ip.is_ipv4(),
+        Some(RecordType::AAAA) => ip.is_ipv6(),
+        _ => true,
+    };
+    if !matches {
+        error!("public ip {ip} does not match record type {record_type:?}");
+        return Ok(None);
+    }
+    Ok(Some(vec![ResolvedContent::Value(ip.to_string())]))
+}
+
+/// Periodically re-discovers the public IP. Will emit object refs of all records using it when it changes, which will
+/// trigger resource updates through [`kube::runtime::Controller::reconcile_on`].
+pub fn start_public_ip_check(
+    public_ip: PublicIp,
+    index: DependencyIndex,
+    check_interval: Duration,
+) -> impl Stream<Item = ObjectRef<CloudflareDNSRecord>> + Send + 'static {
+    async_stream::stream! {
+        let mut timer = tokio::time::interval(check_interval);
+
+        loop {
+            timer.tick().await;
+
+            let dependents = index.dependents(&Dependency::PublicIp);
+            if dependents.is_empty() {
+                continue;
+            }
+
+            match public_ip.refresh().await {
+                Ok((ip, true)) => {
+                    info!("Public ip changed to {ip}, updating {} records", dependents.len());
+                    for record in dependents {
+                        yield record;
+                    }
+                }
+                Ok((ip, false)) => trace!("Public ip is still {ip}"),
+                Err(err) => error!("Failed to discover public ip: {err:#}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sources() {
+        assert_eq!(
+            "https://api.ipify.org".parse::<PublicIpSource>().unwrap(),
+            PublicIpSource::Http("https://api.ipify.org".to_string())
+        );
+        assert_eq!(
+            "dns+ch://1.1.1.1/whoami.cloudflare".parse::<PublicIpSource>().unwrap(),
+            PublicIpSource::Dns {
+                nameserver: "1.1.1.1:53".parse().unwrap(),
+                qname: "whoami.cloudflare".to_string(),
+                class: Class::CH,
+            }
+        );
+        assert_eq!(
+            "dns://216.239.32.10:53/o-o.myaddr.l.google.com"
+                .parse::<PublicIpSource>()
+                .unwrap()
+                .to_string(),
+            "dns://216.239.32.10:53/o-o.myaddr.l.google.com"
+        );
+        assert!("dns://1.1.1.1/".parse::<PublicIpSource>().is_err());
+        assert!("ftp://example.com".parse::<PublicIpSource>().is_err());
+    }
+}
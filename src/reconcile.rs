@@ -1,17 +1,27 @@
 use super::conditions::{
+    drift_condition,
     error_condition,
+    merge_conditions,
     success_condition,
 };
 use crate::{
     context::Context,
-    dns::cloudflare::{
-        self,
-        Zone,
+    credentials,
+    dns::{
+        cloudflare::{
+            self,
+            CloudflareError,
+            Drift,
+            RecordContent,
+            Zone,
+        },
+        provider::DnsProvider,
     },
     dns_check::DnsCheckRequest,
     resources::{
         CloudflareDNSRecord,
         CloudflareDNSRecordStatus,
+        ManagedRecord,
         ZoneNameOrId,
     },
 };
@@ -26,14 +36,20 @@ use kube::{
         Patch,
         PatchParams,
     },
-    runtime::wait,
+    runtime::{
+        events::{
+            Event,
+            EventType,
+        },
+        wait,
+    },
     Api,
+    Resource as _,
 };
-use serde::{
-    Deserialize,
-    Serialize,
+use std::{
+    sync::Arc,
+    time::Duration,
 };
-use std::sync::Arc;
 
 #[derive(thiserror::Error, Debug)]
 pub enum ReconcileError {
@@ -43,15 +59,42 @@ pub enum ReconcileError {
     #[error(transparent)]
     Deletion(#[from] wait::delete::Error),
 
+    #[error(transparent)]
+    Cloudflare(#[from] CloudflareError),
+
+    /// Keeps the context the error was wrapped in. Cloudflare errors passed along this way are still told apart, see
+    /// [`Self::cloudflare_error`].
     #[error(transparent)]
     Other(#[from] eyre::Error),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct AnnotationContent {
-    record_id: String,
-    api_token: String,
-    zone_id: String,
+impl ReconcileError {
+    /// The cloudflare error, also if it was passed along as [`eyre::Report`].
+    pub fn cloudflare_error(&self) -> Option<&CloudflareError> {
+        match self {
+            ReconcileError::Cloudflare(err) => Some(err),
+            ReconcileError::Other(report) => report.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// How long to wait before reconciling again after this error. Errors only a change of the spec (which triggers a
+    /// reconcile anyway) or of the cloudflare account can resolve are retried less often.
+    pub fn requeue_after(&self) -> Duration {
+        const DEFAULT: Duration = Duration::from_secs(60);
+        const RATE_LIMITED: Duration = Duration::from_secs(5 * 60);
+        const PERMANENT: Duration = Duration::from_secs(10 * 60);
+
+        match self.cloudflare_error() {
+            Some(CloudflareError::RateLimited { retry_after }) => retry_after.unwrap_or_default().max(RATE_LIMITED),
+            Some(
+                CloudflareError::Authentication { .. }
+                | CloudflareError::Validation { .. }
+                | CloudflareError::Conflict { .. },
+            ) => PERMANENT,
+            _ => DEFAULT,
+        }
+    }
 }
 
 pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Result<(), ReconcileError> {
@@ -75,7 +118,12 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
         }
     }
 
-    let Some(content) = resource.spec.lookup_content(client, ns).await? else {
+    let contents = resource
+        .spec
+        .lookup_contents(&ctx, ns)
+        .await?
+        .filter(|it| !it.is_empty());
+    let Some(contents) = contents else {
         let msg = format!("unable to resolve content for CloudflareDNSRecord {ns}/{name}");
         error!("{msg}");
         update_conditions(
@@ -107,7 +155,22 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
         return Ok(());
     };
 
-    let Some(zone) = zone.resolve(&ctx.cloudflare_api).await? else {
+    let dns_provider = match ctx.dns_provider_for(&resource).await {
+        Ok(dns_provider) => dns_provider,
+        Err(err) => {
+            let msg = format!("unable to use credentials for CloudflareDNSRecord {ns}/{name}: {err:#}");
+            error!("{msg}");
+            update_conditions(
+                &resource,
+                &ctx,
+                vec![error_condition(&resource, "missing credentials", msg, gen)],
+            )
+            .await?;
+            return Err(err.into());
+        }
+    };
+
+    let Some(zone) = zone.resolve(dns_provider.provider.as_ref()).await? else {
         let msg = format!("unable to resolve zone for CloudflareDNSRecord {ns}/{name}");
         error!("{msg}");
         update_conditions(
@@ -121,22 +184,40 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
     let Zone::Identifier(zone_id) = zone.clone() else {
         unreachable!();
     };
+    if !dns_provider.allows_zone(&zone_id).await? {
+        let msg = format!("the credentials of CloudflareDNSRecord {ns}/{name} do not allow zone {zone_id}");
+        error!("{msg}");
+        update_conditions(
+            &resource,
+            &ctx,
+            vec![error_condition(&resource, "zone not allowed", msg, gen)],
+        )
+        .await?;
+        return Ok(());
+    }
 
-    debug!("updating dns record for CloudflareDNSRecord {ns}/{name}");
-
-    let record = ctx
-        .cloudflare_api
-        .update_dns_record_and_wait(cloudflare::CreateRecordArgs {
-            zone,
-            name: domain_or_record_text.to_string(),
-            record_type: resource.spec.ty.unwrap_or_default(),
-            content,
-            comment: resource.spec.comment.clone(),
-            ttl: resource.spec.ttl,
-        })
+    let record_names = resource
+        .spec
+        .lookup_names(&ctx, dns_provider.provider.as_ref(), ns, &zone_id)
         .await?;
 
-    // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+    debug!("updating dns records {record_names:?} for CloudflareDNSRecord {ns}/{name}");
+
+    // Records created with other credentials are deleted with those, unless they are gone.
+    let previous_provider = match &resource.status {
+        Some(status) if status.credentials != resource.spec.credentials => {
+            match ctx.previous_dns_provider_for(&resource).await {
+                Ok(previous_provider) => Some(previous_provider),
+                Err(err) if credentials::is_not_found(&err) => {
+                    warn!("Unable to delete previous dns records of CloudflareDNSRecord {ns}/{name}: {err:#}");
+                    publish_event(&resource, &ctx, records_left_behind_event(&err)).await;
+                    None
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        _ => Some(dns_provider.clone()),
+    };
 
     let status_key = format!("{ns}:{name}");
 
@@ -150,13 +231,59 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
     } else {
         false
     };
-    let condition = if !pending {
-        success_condition(&resource, gen)
-    } else {
-        let msg = "The DNS record has not propagated yet. This is expected to take some time.".to_string();
-        error_condition(&resource, "pending", msg, gen)
+
+    let synced = sync_records(
+        &resource,
+        dns_provider.provider.as_ref(),
+        previous_provider.as_ref().map(|previous| previous.provider.as_ref()),
+        &zone_id,
+        &record_names,
+        &contents,
+        pending,
+    )
+    .await;
+    let synced = match synced {
+        Ok(synced) => synced,
+        Err(err) => {
+            let msg = format!("unable to update dns record for CloudflareDNSRecord {ns}/{name}: {err:#}");
+            let reason = err
+                .downcast_ref::<CloudflareError>()
+                .map_or("cloudflare error", CloudflareError::reason);
+            update_conditions(&resource, &ctx, vec![error_condition(&resource, reason, msg, gen)]).await?;
+            return Err(err.into());
+        }
     };
 
+    if !synced.drift.is_empty() {
+        let note = synced
+            .drift
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        let event = if synced.drifted {
+            warn!("DNS record for CloudflareDNSRecord {ns}/{name} drifted from the spec: {note}");
+            Event {
+                type_: EventType::Warning,
+                reason: "DriftCorrected".to_string(),
+                note: Some(format!("Cloudflare record was modified outside of kubernetes: {note}")),
+                action: "Update".to_string(),
+                secondary: None,
+            }
+        } else {
+            Event {
+                type_: EventType::Normal,
+                reason: "Updated".to_string(),
+                note: Some(format!("Updated cloudflare record: {note}")),
+                action: "Update".to_string(),
+                secondary: None,
+            }
+        };
+        publish_event(&resource, &ctx, event).await;
+    }
+
+    // -=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-=-
+
     let patched = CloudflareDNSRecord {
         metadata: ObjectMeta {
             name: Some(name.to_string()),
@@ -164,15 +291,7 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
             ..Default::default()
         },
         spec: resource.spec.clone(),
-        status: Some(CloudflareDNSRecordStatus {
-            // We are storing the details about how we created the record in the
-            // status. At deletion time, the configmap / secrets we got the
-            // zone_id from might be gone already.
-            record_id: record.id,
-            zone_id,
-            pending,
-            conditions: Some(vec![condition]),
-        }),
+        status: Some(synced.status),
     };
 
     if is_new && ctx.do_dns_check {
@@ -198,7 +317,116 @@ pub async fn apply(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Res
     Ok(())
 }
 
-/// This functions runs before the resource is deleted. It'll try to delete the DNS record from Cloudflare.
+/// The result of [`sync_records`].
+#[derive(Debug)]
+pub struct SyncedRecordSets {
+    /// The new status of the resource.
+    pub status: CloudflareDNSRecordStatus,
+    /// Attributes of existing records that were corrected.
+    pub drift: Vec<Drift>,
+    /// Whether the records were modified outside of kubernetes, i.e. drifted although the spec did not change.
+    pub drifted: bool,
+}
+
+/// The DNS side of [`apply`]: publishes `contents` under all `names` in zone `zone_id` with `provider` and deletes the
+/// records in the status of `resource` that are no longer published with `previous_provider`, the provider they were
+/// created with. Records that cannot be deleted anymore (`None`) are left behind. Talks to nothing but the providers,
+/// so that reconciliation can be tested with [`InMemoryProvider`](crate::dns::memory::InMemoryProvider).
+pub async fn sync_records(
+    resource: &CloudflareDNSRecord,
+    provider: &dyn DnsProvider,
+    previous_provider: Option<&dyn DnsProvider>,
+    zone_id: &str,
+    names: &[String],
+    contents: &[RecordContent],
+    pending: bool,
+) -> eyre::Result<SyncedRecordSets> {
+    let owner = resource.metadata.uid.as_deref();
+    let gen = resource.metadata.generation;
+
+    let mut records = Vec::new();
+    let mut drift = Vec::new();
+    for name in names {
+        let synced = provider
+            .sync_dns_records(cloudflare::RecordSetArgs {
+                zone: Zone::id(zone_id),
+                name: name.clone(),
+                contents: contents.to_vec(),
+                comment: resource.spec.comment.clone(),
+                ttl: resource.spec.ttl,
+                proxied: resource.spec.proxied,
+                tags: resource.spec.tags.clone(),
+                owner: owner.map(ToString::to_string),
+                ownership: resource.spec.ownership.unwrap_or_default(),
+            })
+            .await?;
+        records.extend(synced.records.into_iter().map(|record| ManagedRecord {
+            id: record.id,
+            name: Some(name.clone()),
+            record_type: record.record_type,
+            content: record.content,
+        }));
+        drift.extend(synced.drift);
+    }
+
+    // Names that are no longer published (e.g. a hostname removed from an HTTPRoute) are cleaned up. After the zone or
+    // the credentials changed, all previous records are, in the zone and with the credentials they were created with.
+    if let (Some(status), Some(owner)) = (resource.status.as_ref(), owner) {
+        let moved = status.zone_id != zone_id || status.credentials != resource.spec.credentials;
+        let stale = status
+            .records
+            .iter()
+            .flatten()
+            .filter(|record| moved || record.name.as_ref().is_some_and(|it| !names.contains(it)))
+            .filter(|record| !records.iter().any(|managed| managed.id == record.id));
+        for record in stale {
+            let Some(previous_provider) = previous_provider else {
+                warn!("leaving dns record {:?} ({}) behind", record.name, record.id);
+                continue;
+            };
+            info!("deleting dns record {:?} that is no longer published", record.name);
+            previous_provider
+                .delete_owned_dns_record(&status.zone_id, &record.id, owner)
+                .await?;
+        }
+    }
+
+    // If the spec did not change since we last applied it, any difference to the cloudflare record was introduced
+    // outside of kubernetes.
+    let last_applied_generation = resource
+        .status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .and_then(|conditions| conditions.iter().find(|condition| condition.type_ == "Ready"))
+        .and_then(|condition| condition.observed_generation);
+    let drifted = !drift.is_empty() && last_applied_generation.is_some() && last_applied_generation == gen;
+
+    let condition = if !pending {
+        success_condition(resource, gen)
+    } else {
+        let msg = "The DNS record has not propagated yet. This is expected to take some time.".to_string();
+        error_condition(resource, "pending", msg, gen)
+    };
+
+    let status = CloudflareDNSRecordStatus {
+        // We are storing the details about how we created the record in the
+        // status. At deletion time, the configmap / secrets we got the
+        // zone_id from might be gone already.
+        record_id: records.first().map(|record| record.id.clone()).unwrap_or_default(),
+        records: Some(records),
+        zone_id: zone_id.to_string(),
+        credentials: resource.spec.credentials.clone(),
+        pending,
+        conditions: Some(vec![
+            condition,
+            drift_condition(resource, if drifted { &drift } else { &[] }, gen),
+        ]),
+    };
+
+    Ok(SyncedRecordSets { status, drift, drifted })
+}
+
+/// This functions runs before the resource is deleted. It'll try to delete the DNS records from Cloudflare.
 #[instrument(level = "debug", skip_all)]
 pub async fn cleanup(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> Result<(), ReconcileError> {
     let ns = resource.metadata.namespace.as_deref().unwrap_or("default");
@@ -211,17 +439,84 @@ pub async fn cleanup(resource: Arc<CloudflareDNSRecord>, ctx: Arc<Context>) -> R
         return Ok(());
     };
 
-    if let Err(err) = ctx
-        .cloudflare_api
-        .delete_dns_record(&status.zone_id, &status.record_id)
-        .await
-    {
-        error!("Unable to delete dns record for cloudflare: {err}");
+    let Some(owner) = resource.metadata.uid.as_deref() else {
+        error!("missing uid for CloudflareDNSRecord {ns}/{name}");
+        return Ok(());
+    };
+
+    // Without credentials the records cannot be deleted. If they are gone for good, e.g. because the namespace is being
+    // deleted, the records are left behind instead of keeping the resource forever.
+    let dns_provider = match ctx.previous_dns_provider_for(&resource).await {
+        Ok(dns_provider) => dns_provider,
+        Err(err) if credentials::is_not_found(&err) => {
+            warn!("Unable to delete dns records of CloudflareDNSRecord {ns}/{name}, leaving them behind: {err:#}");
+            publish_event(&resource, &ctx, records_left_behind_event(&err)).await;
+            return Ok(());
+        }
+        Err(err) => {
+            return Err(err
+                .wrap_err(format!(
+                    "unable to delete dns records of CloudflareDNSRecord {ns}/{name}"
+                ))
+                .into())
+        }
+    };
+    if !dns_provider.allows_zone(&status.zone_id).await? {
+        error!(
+            "the credentials of CloudflareDNSRecord {ns}/{name} do not allow zone {}",
+            status.zone_id
+        );
+        return Ok(());
     }
 
+    delete_records(dns_provider.provider.as_ref(), status, owner).await;
+
     Ok(())
 }
 
+/// The DNS side of [`cleanup`]: deletes the records in `status` owned by `owner` with `provider`. Records that cannot
+/// be deleted are logged and left behind.
+pub async fn delete_records(provider: &dyn DnsProvider, status: &CloudflareDNSRecordStatus, owner: &str) {
+    let mut record_ids = status
+        .records
+        .iter()
+        .flatten()
+        .map(|record| record.id.as_str())
+        .collect::<Vec<_>>();
+    if !status.record_id.is_empty() && !record_ids.contains(&status.record_id.as_str()) {
+        record_ids.push(&status.record_id);
+    }
+
+    for record_id in record_ids {
+        if let Err(err) = provider
+            .delete_owned_dns_record(&status.zone_id, record_id, owner)
+            .await
+        {
+            error!("Unable to delete dns record for cloudflare: {err}");
+        }
+    }
+}
+
+/// Warns that the cloudflare records of a resource are not deleted, because its credentials are gone.
+fn records_left_behind_event(err: &eyre::Report) -> Event {
+    Event {
+        type_: EventType::Warning,
+        reason: "RecordsLeftBehind".to_string(),
+        note: Some(format!(
+            "Cloudflare records were not deleted, the credentials are gone: {err:#}"
+        )),
+        action: "Delete".to_string(),
+        secondary: None,
+    }
+}
+
+/// Publishes a kubernetes event for `resource`. Failing to do so is logged but not considered a reconcile error.
+async fn publish_event(resource: &CloudflareDNSRecord, ctx: &Context, event: Event) {
+    if let Err(err) = ctx.recorder.publish(&event, &resource.object_ref(&())).await {
+        warn!("Unable to publish event {:?}: {err}", event.reason);
+    }
+}
+
 pub async fn update_conditions(
     resource: &CloudflareDNSRecord,
     ctx: &Context,
@@ -239,7 +534,7 @@ pub async fn update_conditions(
         },
         spec: resource.spec.clone(),
         status: Some(CloudflareDNSRecordStatus {
-            conditions: Some(conditions),
+            conditions: Some(merge_conditions(resource, conditions)),
             ..status
         }),
     };
@@ -251,3 +546,216 @@ pub async fn update_conditions(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dns::{
+            cloudflare::{
+                CreateRecordArgs,
+                DnsRecordPatch,
+            },
+            memory::InMemoryProvider,
+            provider::ZoneInfo,
+        },
+        resources::RecordType,
+    };
+
+    fn provider(zone_id: &str, zone_name: &str) -> InMemoryProvider {
+        InMemoryProvider::new([ZoneInfo {
+            id: zone_id.to_string(),
+            name: zone_name.to_string(),
+        }])
+    }
+
+    fn resource() -> CloudflareDNSRecord {
+        let mut resource = CloudflareDNSRecord::new(
+            "foo",
+            serde_json::from_value(serde_json::json!({
+                "name": "foo.example.com",
+                "content": { "value": "1.2.3.4" },
+                "zone": { "name": { "value": "example.com" } },
+            }))
+            .unwrap(),
+        );
+        resource.metadata.namespace = Some("default".to_string());
+        resource.metadata.uid = Some("uid-1".to_string());
+        resource.metadata.generation = Some(1);
+        resource
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    fn a(content: &str) -> Vec<RecordContent> {
+        vec![RecordContent::new(RecordType::A, content)]
+    }
+
+    fn condition<'a>(status: &'a CloudflareDNSRecordStatus, type_: &str) -> &'a Condition {
+        status
+            .conditions
+            .iter()
+            .flatten()
+            .find(|condition| condition.type_ == type_)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn records_are_published_under_all_names_and_drift_is_reported() {
+        let dns = provider("zone1", "example.com");
+        let mut resource = resource();
+        let names = names(&["foo.example.com", "bar.example.com"]);
+
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &names, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        let records = synced.status.records.clone().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(synced.status.record_id, records[0].id);
+        assert_eq!(synced.status.zone_id, "zone1");
+        assert_eq!(condition(&synced.status, "Ready").status, "True");
+        assert_eq!(condition(&synced.status, "Drifted").status, "False");
+        resource.status = Some(synced.status);
+
+        // Modified outside of kubernetes while the spec stayed the same.
+        let patch = DnsRecordPatch {
+            content: Some("6.6.6.6".to_string()),
+            ..Default::default()
+        };
+        dns.update_dns_record("zone1", &records[1].id, patch).await.unwrap();
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &names, &a("1.2.3.4"), true)
+            .await
+            .unwrap();
+        assert!(synced.drifted);
+        assert_eq!(synced.drift[0].field, "content");
+        assert_eq!(condition(&synced.status, "Drifted").status, "True");
+        assert_eq!(condition(&synced.status, "Ready").reason, "pending");
+        assert!(dns.records("zone1").iter().all(|record| record.content == "1.2.3.4"));
+    }
+
+    #[tokio::test]
+    async fn records_of_others_are_not_modified() {
+        let dns = provider("zone1", "example.com");
+        dns.create_dns_record(CreateRecordArgs {
+            zone: Zone::id("zone1"),
+            name: "foo.example.com".to_string(),
+            record_type: RecordType::A,
+            content: "9.9.9.9".to_string(),
+            priority: None,
+            data: None,
+            comment: None,
+            ttl: None,
+            proxied: None,
+            tags: None,
+            owner: Some("uid-2".to_string()),
+        })
+        .await
+        .unwrap();
+
+        let names = names(&["foo.example.com"]);
+        let result = sync_records(&resource(), &dns, Some(&dns), "zone1", &names, &a("1.2.3.4"), false).await;
+        assert!(result.is_err());
+        let records = dns.records("zone1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].content, "9.9.9.9");
+
+        delete_records(
+            &dns,
+            &CloudflareDNSRecordStatus {
+                record_id: records[0].id.clone(),
+                zone_id: "zone1".to_string(),
+                ..Default::default()
+            },
+            "uid-1",
+        )
+        .await;
+        assert_eq!(dns.records("zone1").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn stale_records_are_deleted_with_the_provider_they_were_created_with() {
+        let dns = provider("zone1", "example.com");
+        let mut resource = resource();
+
+        let both = names(&["foo.example.com", "bar.example.com"]);
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &both, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        resource.status = Some(synced.status);
+
+        // A name that is no longer published.
+        let foo = names(&["foo.example.com"]);
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &foo, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        assert_eq!(dns.records("zone1").len(), 1);
+        resource.status = Some(synced.status);
+
+        // Other credentials, managing another zone.
+        let other = provider("zone2", "example.org");
+        resource.spec.credentials = Some("team-a".to_string());
+        let synced = sync_records(&resource, &other, Some(&dns), "zone2", &foo, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        assert!(dns.records("zone1").is_empty());
+        assert_eq!(other.records("zone2").len(), 1);
+        assert_eq!(synced.status.credentials.as_deref(), Some("team-a"));
+        resource.status = Some(synced.status);
+
+        // Without the previous credentials, the records are left behind.
+        resource.spec.credentials = None;
+        sync_records(&resource, &dns, None, "zone1", &foo, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        assert_eq!(other.records("zone2").len(), 1);
+        assert_eq!(dns.records("zone1").len(), 1);
+
+        delete_records(&dns, &resource.status.clone().unwrap(), "uid-1").await;
+        assert_eq!(other.records("zone2").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn records_make_room_for_a_cname() {
+        let dns = provider("zone1", "example.com");
+        let mut resource = resource();
+        let foo = names(&["foo.example.com"]);
+
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &foo, &a("1.2.3.4"), false)
+            .await
+            .unwrap();
+        resource.status = Some(synced.status);
+
+        let cname = vec![RecordContent::new(RecordType::CNAME, "lb.example.net")];
+        let synced = sync_records(&resource, &dns, Some(&dns), "zone1", &foo, &cname, false)
+            .await
+            .unwrap();
+        let records = dns.records("zone1");
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].record_type, "CNAME");
+        resource.status = Some(synced.status);
+
+        delete_records(&dns, resource.status.as_ref().unwrap(), "uid-1").await;
+        assert!(dns.records("zone1").is_empty());
+    }
+
+    #[test]
+    fn cloudflare_errors_stay_typed() {
+        let report = eyre::Report::new(CloudflareError::RateLimited {
+            retry_after: Some(Duration::from_secs(600)),
+        })
+        .wrap_err("Failed to update existing DNS record");
+        let err = ReconcileError::from(report);
+        assert!(matches!(
+            err.cloudflare_error(),
+            Some(CloudflareError::RateLimited { .. })
+        ));
+        assert_eq!(err.requeue_after(), Duration::from_secs(600));
+        assert!(format!("{err:#}").starts_with("Failed to update existing DNS record: "));
+
+        let err = ReconcileError::from(eyre::eyre!("zone not found"));
+        assert!(err.cloudflare_error().is_none());
+        assert_eq!(err.requeue_after(), Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,1054 @@
+//! Structured data of record types whose content consists of several fields, such as MX, SRV or CAA records. The data
+//! can be given as structured content in the spec, or as a string in zone file format (e.g. `10 mail.example.com` for
+//! MX), which is useful for contents coming from templates or references.
+
+use crate::resources::RecordType;
+use eyre::{
+    bail,
+    Context as _,
+    Result,
+};
+use schemars::JsonSchema;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::{
+    json,
+    Value,
+};
+use std::{
+    net::IpAddr,
+    str::FromStr,
+};
+
+/// Data of an MX record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct MxData {
+    /// Preference of the mail server, lower values are preferred
+    pub priority: u16,
+    /// Hostname of the mail server
+    pub exchange: String,
+}
+
+/// Data of an SRV record. The name of the record carries service and protocol, e.g. `_sip._tcp.example.com`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SrvData {
+    /// Priority of the target host, lower values are preferred
+    pub priority: u16,
+    /// Relative weight for records with the same priority
+    pub weight: u16,
+    /// Port of the service on the target host
+    pub port: u16,
+    /// Hostname of the target host
+    pub target: String,
+}
+
+/// Data of a CAA record, e.g. `0 issue "letsencrypt.org"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CaaData {
+    /// Flags, 128 marks the property as critical
+    pub flags: u8,
+    /// Property tag, e.g. `issue`, `issuewild` or `iodef`
+    pub tag: String,
+    /// Property value, e.g. the domain of a certificate authority
+    pub value: String,
+}
+
+/// Data of an HTTPS or SVCB record, e.g. `1 . alpn="h3,h2"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SvcbData {
+    /// Priority, 0 marks an alias record
+    pub priority: u16,
+    /// Target name, `.` for the owner name of the record
+    pub target: String,
+    /// Service parameters in zone file format, e.g. `alpn="h3,h2" ipv4hint="1.2.3.4"`
+    pub value: String,
+}
+
+/// Data of a TLSA record, e.g. `3 1 1 <sha256 of the public key>`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct TlsaData {
+    /// Certificate usage, e.g. 3 for a domain issued certificate
+    pub usage: u8,
+    /// Selector, 0 for the full certificate, 1 for the public key
+    pub selector: u8,
+    /// Matching type, 0 for the exact data, 1 for SHA-256, 2 for SHA-512
+    pub matching_type: u8,
+    /// Certificate association data, hex encoded
+    pub certificate: String,
+}
+
+/// Data of a DS record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DsData {
+    /// Key tag of the referenced DNSKEY
+    pub key_tag: u16,
+    /// Algorithm of the referenced DNSKEY
+    pub algorithm: u8,
+    /// Digest type, e.g. 2 for SHA-256
+    pub digest_type: u8,
+    /// Digest of the referenced DNSKEY, hex encoded
+    pub digest: String,
+}
+
+/// Data of an SSHFP record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SshfpData {
+    /// Algorithm of the host key, e.g. 4 for Ed25519
+    pub algorithm: u8,
+    /// Fingerprint type, 1 for SHA-1, 2 for SHA-256
+    #[serde(rename = "type")]
+    pub ty: u8,
+    /// Fingerprint of the host key, hex encoded
+    pub fingerprint: String,
+}
+
+/// Data of a NAPTR record, e.g. `100 10 "S" "SIP+D2U" "" _sip._udp.example.com`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct NaptrData {
+    /// Order in which the records must be processed, lower values first
+    pub order: u16,
+    /// Preference of records with the same order, lower values are preferred
+    pub preference: u16,
+    /// Flags controlling the rewriting, e.g. `S`, `A`, `U` or `P`
+    pub flags: String,
+    /// Service parameters, e.g. `SIP+D2U`
+    pub service: String,
+    /// Substitution expression applied to the original string
+    pub regex: String,
+    /// Replacement name, `.` if the regex is used
+    pub replacement: String,
+}
+
+/// Data of a CERT record.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CertData {
+    /// Certificate type, e.g. 1 for X.509 (PKIX)
+    #[serde(rename = "type")]
+    pub ty: u16,
+    /// Key tag of the certificate
+    pub key_tag: u16,
+    /// Algorithm of the certificate
+    pub algorithm: u8,
+    /// The certificate, base64 encoded
+    pub certificate: String,
+}
+
+/// Data of a URI record, e.g. `10 1 "https://example.com"`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct UriData {
+    /// Priority of the target URI, lower values are preferred
+    pub priority: u16,
+    /// Relative weight for records with the same priority
+    pub weight: u16,
+    /// The target URI
+    pub target: String,
+}
+
+/// Data of a LOC record ([RFC 1876](https://www.rfc-editor.org/rfc/rfc1876)), e.g.
+/// `52 31 12.000 N 13 24 36.000 E 34.00m 1.00m 10000.00m 10.00m`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LocData {
+    /// Degrees of latitude
+    #[schemars(range(max = 90))]
+    pub lat_degrees: u8,
+    /// Minutes of latitude
+    #[serde(default)]
+    #[schemars(range(max = 59))]
+    pub lat_minutes: u8,
+    /// Seconds of latitude
+    #[serde(default)]
+    #[schemars(range(min = 0, max = 59.999))]
+    pub lat_seconds: f64,
+    /// Hemisphere of the latitude
+    pub lat_direction: LatitudeDirection,
+    /// Degrees of longitude
+    #[schemars(range(max = 180))]
+    pub long_degrees: u8,
+    /// Minutes of longitude
+    #[serde(default)]
+    #[schemars(range(max = 59))]
+    pub long_minutes: u8,
+    /// Seconds of longitude
+    #[serde(default)]
+    #[schemars(range(min = 0, max = 59.999))]
+    pub long_seconds: f64,
+    /// Hemisphere of the longitude
+    pub long_direction: LongitudeDirection,
+    /// Altitude in meters
+    #[serde(default)]
+    #[schemars(range(min = -100000, max = 42849672.95))]
+    pub altitude: f64,
+    /// Diameter of the sphere enclosing the location in meters. Defaults to 1.
+    #[serde(default = "LocData::default_size")]
+    #[schemars(range(min = 0, max = 90000000))]
+    pub size: f64,
+    /// Horizontal precision in meters. Defaults to 10000.
+    #[serde(default = "LocData::default_precision_horz")]
+    #[schemars(range(min = 0, max = 90000000))]
+    pub precision_horz: f64,
+    /// Vertical precision in meters. Defaults to 10.
+    #[serde(default = "LocData::default_precision_vert")]
+    #[schemars(range(min = 0, max = 90000000))]
+    pub precision_vert: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum LatitudeDirection {
+    N,
+    S,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum LongitudeDirection {
+    E,
+    W,
+}
+
+impl LocData {
+    fn default_size() -> f64 {
+        1.0
+    }
+
+    fn default_precision_horz() -> f64 {
+        10000.0
+    }
+
+    fn default_precision_vert() -> f64 {
+        10.0
+    }
+
+    /// The data as it is stored in DNS: seconds are rounded to milliseconds, the altitude to centimeters and size and
+    /// precisions to a single digit times a power of ten centimeters.
+    pub fn canonical(&self) -> Self {
+        Self {
+            lat_seconds: (self.lat_seconds * 1000.0).round() / 1000.0,
+            long_seconds: (self.long_seconds * 1000.0).round() / 1000.0,
+            altitude: (self.altitude * 100.0).round() / 100.0,
+            size: decode_loc_size(encode_loc_size(self.size)),
+            precision_horz: decode_loc_size(encode_loc_size(self.precision_horz)),
+            precision_vert: decode_loc_size(encode_loc_size(self.precision_vert)),
+            ..self.clone()
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let valid = |degrees: u8, max_degrees: u8, minutes: u8, seconds: f64| {
+            degrees <= max_degrees
+                && minutes < 60
+                && (0.0..60.0).contains(&seconds)
+                && (degrees < max_degrees || (minutes == 0 && seconds == 0.0))
+        };
+        if !valid(self.lat_degrees, 90, self.lat_minutes, self.lat_seconds) {
+            bail!("invalid latitude in {self}");
+        }
+        if !valid(self.long_degrees, 180, self.long_minutes, self.long_seconds) {
+            bail!("invalid longitude in {self}");
+        }
+        if !(-100_000.0..=42_849_672.95).contains(&self.altitude) {
+            bail!("invalid altitude {}", self.altitude);
+        }
+        for (value, field) in [
+            (self.size, "size"),
+            (self.precision_horz, "horizontal precision"),
+            (self.precision_vert, "vertical precision"),
+        ] {
+            if !(0.0..=90_000_000.0).contains(&value) {
+                bail!("invalid {field} {value}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Encodes a size or precision in meters the way LOC records store it: the high nibble is a digit, the low nibble
+/// the power of ten of centimeters it is multiplied with.
+pub fn encode_loc_size(meters: f64) -> u8 {
+    let mut centimeters = (meters * 100.0).round() as u64;
+    let mut exponent = 0;
+    while centimeters >= 10 && exponent < 9 {
+        centimeters /= 10;
+        exponent += 1;
+    }
+    ((centimeters.min(9) as u8) << 4) | exponent
+}
+
+/// Decodes a size or precision encoded with [`encode_loc_size`] to meters.
+pub fn decode_loc_size(encoded: u8) -> f64 {
+    let digit = (encoded >> 4).min(9) as f64;
+    let exponent = (encoded & 0x0f).min(9) as i32;
+    digit * 10f64.powi(exponent) / 100.0
+}
+
+impl std::fmt::Display for MxData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.exchange)
+    }
+}
+
+impl FromStr for MxData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [priority, exchange] = fields(s)?;
+        Ok(MxData {
+            priority: number(&priority, "priority")?,
+            exchange: hostname(&exchange),
+        })
+    }
+}
+
+impl std::fmt::Display for SrvData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {} {}", self.priority, self.weight, self.port, self.target)
+    }
+}
+
+impl FromStr for SrvData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [priority, weight, port, target] = fields(s)?;
+        Ok(SrvData {
+            priority: number(&priority, "priority")?,
+            weight: number(&weight, "weight")?,
+            port: number(&port, "port")?,
+            target: hostname(&target),
+        })
+    }
+}
+
+impl std::fmt::Display for CaaData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.flags, self.tag, quoted(&self.value))
+    }
+}
+
+impl FromStr for CaaData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [flags, tag, value] = fields(s)?;
+        Ok(CaaData {
+            flags: number(&flags, "flags")?,
+            tag,
+            value,
+        })
+    }
+}
+
+impl SvcbData {
+    fn normalized(self) -> Self {
+        SvcbData {
+            priority: self.priority,
+            target: normalize_hostname(&self.target),
+            value: normalize_svc_params(&self.value),
+        }
+    }
+}
+
+impl std::fmt::Display for SvcbData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.priority, self.target)?;
+        if !self.value.is_empty() {
+            write!(f, " {}", self.value)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SvcbData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the service parameters are kept as they are, cloudflare expects them in zone file format
+        let (priority, rest) = s.trim().split_once(char::is_whitespace).unwrap_or((s.trim(), ""));
+        let (target, value) = rest.trim().split_once(char::is_whitespace).unwrap_or((rest.trim(), ""));
+        if target.is_empty() {
+            bail!("expected a priority and a target, got {s:?}");
+        }
+        Ok(SvcbData {
+            priority: number(priority, "priority")?,
+            target: hostname(target),
+            value: value.trim().to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for TlsaData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.usage, self.selector, self.matching_type, self.certificate
+        )
+    }
+}
+
+impl FromStr for TlsaData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [usage, selector, matching_type, certificate] = fields(s)?;
+        Ok(TlsaData {
+            usage: number(&usage, "usage")?,
+            selector: number(&selector, "selector")?,
+            matching_type: number(&matching_type, "matching type")?,
+            certificate: hex(certificate, "certificate")?,
+        })
+    }
+}
+
+impl std::fmt::Display for DsData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.key_tag, self.algorithm, self.digest_type, self.digest
+        )
+    }
+}
+
+impl FromStr for DsData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [key_tag, algorithm, digest_type, digest] = fields(s)?;
+        Ok(DsData {
+            key_tag: number(&key_tag, "key tag")?,
+            algorithm: number(&algorithm, "algorithm")?,
+            digest_type: number(&digest_type, "digest type")?,
+            digest: hex(digest, "digest")?,
+        })
+    }
+}
+
+impl std::fmt::Display for SshfpData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.algorithm, self.ty, self.fingerprint)
+    }
+}
+
+impl FromStr for SshfpData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [algorithm, ty, fingerprint] = fields(s)?;
+        Ok(SshfpData {
+            algorithm: number(&algorithm, "algorithm")?,
+            ty: number(&ty, "fingerprint type")?,
+            fingerprint: hex(fingerprint, "fingerprint")?,
+        })
+    }
+}
+
+impl std::fmt::Display for NaptrData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.order,
+            self.preference,
+            quoted(&self.flags),
+            quoted(&self.service),
+            quoted(&self.regex),
+            self.replacement
+        )
+    }
+}
+
+impl FromStr for NaptrData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [order, preference, flags, service, regex, replacement] = fields(s)?;
+        Ok(NaptrData {
+            order: number(&order, "order")?,
+            preference: number(&preference, "preference")?,
+            flags,
+            service,
+            regex,
+            replacement: hostname(&replacement),
+        })
+    }
+}
+
+impl std::fmt::Display for CertData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            self.ty, self.key_tag, self.algorithm, self.certificate
+        )
+    }
+}
+
+impl FromStr for CertData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [ty, key_tag, algorithm, certificate] = fields(s)?;
+        Ok(CertData {
+            ty: number(&ty, "certificate type")?,
+            key_tag: number(&key_tag, "key tag")?,
+            algorithm: number(&algorithm, "algorithm")?,
+            certificate,
+        })
+    }
+}
+
+impl std::fmt::Display for UriData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.priority, self.weight, quoted(&self.target))
+    }
+}
+
+impl FromStr for UriData {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [priority, weight, target] = fields(s)?;
+        Ok(UriData {
+            priority: number(&priority, "priority")?,
+            weight: number(&weight, "weight")?,
+            target,
+        })
+    }
+}
+
+impl std::fmt::Display for LocData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {:.3} {:?} {} {} {:.3} {:?} {:.2}m {:.2}m {:.2}m {:.2}m",
+            self.lat_degrees,
+            self.lat_minutes,
+            self.lat_seconds,
+            self.lat_direction,
+            self.long_degrees,
+            self.long_minutes,
+            self.long_seconds,
+            self.long_direction,
+            self.altitude,
+            self.size,
+            self.precision_horz,
+            self.precision_vert
+        )
+    }
+}
+
+impl FromStr for LocData {
+    type Err = eyre::Report;
+
+    /// Parses `d1 [m1 [s1]] N|S d2 [m2 [s2]] E|W alt[m] [size[m] [hp[m] [vp[m]]]]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = s.split_whitespace();
+
+        let (lat_degrees, lat_minutes, lat_seconds, lat_direction) = coordinate(&mut tokens, ["N", "S"])?;
+        let (long_degrees, long_minutes, long_seconds, long_direction) = coordinate(&mut tokens, ["E", "W"])?;
+
+        let mut meters = |field: &str| -> Result<Option<f64>> {
+            tokens
+                .next()
+                .map(|token| number(token.trim_end_matches('m'), field))
+                .transpose()
+        };
+        let altitude = meters("altitude")?.ok_or_else(|| eyre::eyre!("missing altitude"))?;
+        let size = meters("size")?.unwrap_or(LocData::default_size());
+        let precision_horz = meters("horizontal precision")?.unwrap_or(LocData::default_precision_horz());
+        let precision_vert = meters("vertical precision")?.unwrap_or(LocData::default_precision_vert());
+        if let Some(token) = tokens.next() {
+            bail!("unexpected {token:?}");
+        }
+
+        let loc = LocData {
+            lat_degrees,
+            lat_minutes,
+            lat_seconds,
+            lat_direction: if lat_direction == "N" {
+                LatitudeDirection::N
+            } else {
+                LatitudeDirection::S
+            },
+            long_degrees,
+            long_minutes,
+            long_seconds,
+            long_direction: if long_direction == "E" {
+                LongitudeDirection::E
+            } else {
+                LongitudeDirection::W
+            },
+            altitude,
+            size,
+            precision_horz,
+            precision_vert,
+        };
+        loc.validate()?;
+        Ok(loc)
+    }
+}
+
+/// Reads the degrees, optional minutes and seconds and the direction of a LOC coordinate.
+fn coordinate<'a>(tokens: &mut impl Iterator<Item = &'a str>, directions: [&str; 2]) -> Result<(u8, u8, f64, &'a str)> {
+    let mut parts = Vec::new();
+    let direction = loop {
+        match tokens.next() {
+            Some(token) if directions.contains(&token) => break token,
+            Some(token) if parts.len() < 3 => parts.push(token),
+            _ => bail!("expected degrees, minutes, seconds and {}", directions.join(" or ")),
+        }
+    };
+    let Some(degrees) = parts.first() else {
+        bail!("missing degrees before {direction}");
+    };
+    Ok((
+        number(degrees, "degrees")?,
+        parts.get(1).map(|it| number(it, "minutes")).transpose()?.unwrap_or(0),
+        parts.get(2).map(|it| number(it, "seconds")).transpose()?.unwrap_or(0.0),
+        direction,
+    ))
+}
+
+/// Splits a zone file formatted record content into exactly `N` fields, see [`tokens`].
+fn fields<const N: usize>(s: &str) -> Result<[String; N]> {
+    match tokens(s)?.try_into() {
+        Ok(fields) => Ok(fields),
+        Err(fields) => bail!("expected {N} fields, got {}: {s:?}", fields.len()),
+    }
+}
+
+/// Splits a zone file formatted string at whitespace outside of quotes. Quotes are removed, backslash escapes the next
+/// character.
+fn tokens(s: &str) -> Result<Vec<String>> {
+    let mut tokens = Vec::new();
+    let mut token = None::<String>;
+    let mut quoted = false;
+    let mut chars = s.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                token.get_or_insert_default();
+            }
+            '\\' => token.get_or_insert_default().extend(chars.next()),
+            c if c.is_whitespace() && !quoted => tokens.extend(token.take()),
+            c => token.get_or_insert_default().push(c),
+        }
+    }
+    if quoted {
+        bail!("unterminated quote in {s:?}");
+    }
+    tokens.extend(token);
+    Ok(tokens)
+}
+
+/// Quotes a field for the zone file format.
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn number<T: FromStr>(s: &str, field: &str) -> Result<T>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    s.parse().with_context(|| format!("invalid {field} {s:?}"))
+}
+
+fn hex(s: String, field: &str) -> Result<String> {
+    if s.is_empty() || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("invalid {field} {s:?}, expected hex");
+    }
+    Ok(s)
+}
+
+/// Names of the SVCB / HTTPS service parameter keys, indexed by their number.
+pub const SVC_PARAM_KEYS: [&str; 7] = [
+    "mandatory",
+    "alpn",
+    "no-default-alpn",
+    "port",
+    "ipv4hint",
+    "ech",
+    "ipv6hint",
+];
+
+/// Canonical form of a record content, so that notations which are equivalent in DNS compare equal: IP addresses are
+/// formatted canonically, hostnames are lowercase without trailing dot, quoted and chunked TXT values are joined and
+/// record data is formatted uniformly. Used to compare desired contents with cloudflare records and DNS answers.
+pub fn normalize_content(record_type: RecordType, content: &str) -> String {
+    let content = content.trim();
+    match record_type {
+        RecordType::A | RecordType::AAAA => match content.parse::<IpAddr>() {
+            Ok(ip) => ip.to_string(),
+            Err(_) => content.to_string(),
+        },
+        RecordType::CNAME | RecordType::NS | RecordType::PTR => normalize_hostname(content),
+        RecordType::TXT | RecordType::SPF if content.starts_with('"') => match tokens(content) {
+            // cloudflare splits values longer than 255 characters into several quoted strings
+            Ok(chunks) => chunks.concat(),
+            Err(_) => content.to_string(),
+        },
+        RecordType::TXT | RecordType::SPF => content.to_string(),
+        ty => match RecordData::parse(ty, content) {
+            Ok(Some(data)) => data.normalized().to_string(),
+            // e.g. the cloudflare content of MX and SRV records, which lacks the priority
+            _ => content
+                .split_whitespace()
+                .map(normalize_hostname)
+                .collect::<Vec<_>>()
+                .join(" "),
+        },
+    }
+}
+
+fn normalize_hostname(s: &str) -> String {
+    hostname(s).to_ascii_lowercase()
+}
+
+/// Service parameters with lowercase keys, unquoted values and ordered by key number like DNS answers return them.
+fn normalize_svc_params(params: &str) -> String {
+    let Ok(params) = tokens(params) else {
+        return params.to_string();
+    };
+    let mut params = params
+        .iter()
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.to_ascii_lowercase(), Some(value)),
+            None => (param.to_ascii_lowercase(), None),
+        })
+        .collect::<Vec<_>>();
+    let number = |key: &str| match SVC_PARAM_KEYS.iter().position(|name| *name == key) {
+        Some(number) => number as u32,
+        None => key.strip_prefix("key").and_then(|n| n.parse().ok()).unwrap_or(u32::MAX),
+    };
+    params.sort_by_key(|(key, _)| number(key));
+    params
+        .into_iter()
+        .map(|(key, value)| match value {
+            Some(value) => format!("{key}={value}"),
+            None => key,
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Hostnames are stored without the trailing dot, the way cloudflare returns them. The root name stays `.`.
+fn hostname(s: &str) -> String {
+    match s.trim_end_matches('.') {
+        "" => ".".to_string(),
+        name => name.to_string(),
+    }
+}
+
+/// Record data of a record type that cloudflare does not accept as plain `content`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordData {
+    Mx(MxData),
+    Srv(SrvData),
+    Caa(CaaData),
+    Https(SvcbData),
+    Svcb(SvcbData),
+    Tlsa(TlsaData),
+    Ds(DsData),
+    Sshfp(SshfpData),
+    Naptr(NaptrData),
+    Cert(CertData),
+    Uri(UriData),
+    Loc(LocData),
+}
+
+impl RecordData {
+    /// Parses the zone file format of `record_type`. Returns `None` for types whose content is a plain string.
+    pub fn parse(record_type: RecordType, s: &str) -> Result<Option<Self>> {
+        let data = match record_type {
+            RecordType::MX => RecordData::Mx(s.parse().context("invalid MX record")?),
+            RecordType::SRV => RecordData::Srv(s.parse().context("invalid SRV record")?),
+            RecordType::CAA => RecordData::Caa(s.parse().context("invalid CAA record")?),
+            RecordType::HTTPS => RecordData::Https(s.parse().context("invalid HTTPS record")?),
+            RecordType::SVCB => RecordData::Svcb(s.parse().context("invalid SVCB record")?),
+            RecordType::TLSA => RecordData::Tlsa(s.parse().context("invalid TLSA record")?),
+            RecordType::DS => RecordData::Ds(s.parse().context("invalid DS record")?),
+            RecordType::SSHFP => RecordData::Sshfp(s.parse().context("invalid SSHFP record")?),
+            RecordType::NAPTR => RecordData::Naptr(s.parse().context("invalid NAPTR record")?),
+            RecordType::CERT => RecordData::Cert(s.parse().context("invalid CERT record")?),
+            RecordType::URI => RecordData::Uri(s.parse().context("invalid URI record")?),
+            RecordType::LOC => RecordData::Loc(s.parse::<LocData>().context("invalid LOC record")?.canonical()),
+            _ => return Ok(None),
+        };
+        Ok(Some(data))
+    }
+
+    /// Reads the `data` object of a cloudflare record of `record_type`, the counterpart of [`Self::cloudflare_data`].
+    /// `priority` is the priority of the record, which is not part of the data of URI records. Returns `None` for
+    /// types without structured data or data that does not fit the type.
+    pub fn from_cloudflare_data(record_type: RecordType, data: &Value, priority: Option<u16>) -> Option<Self> {
+        fn read<T: serde::de::DeserializeOwned>(data: &Value) -> Option<T> {
+            serde_json::from_value(data.clone()).ok()
+        }
+        let data = match record_type {
+            RecordType::SRV => RecordData::Srv(read(data)?),
+            RecordType::CAA => RecordData::Caa(read(data)?),
+            RecordType::HTTPS => RecordData::Https(read(data)?),
+            RecordType::SVCB => RecordData::Svcb(read(data)?),
+            RecordType::TLSA => RecordData::Tlsa(read(data)?),
+            RecordType::DS => RecordData::Ds(read(data)?),
+            RecordType::SSHFP => RecordData::Sshfp(read(data)?),
+            RecordType::NAPTR => RecordData::Naptr(read(data)?),
+            RecordType::CERT => RecordData::Cert(read(data)?),
+            RecordType::LOC => RecordData::Loc(read::<LocData>(data)?.canonical()),
+            RecordType::URI => {
+                let mut data = data.clone();
+                data.as_object_mut()?
+                    .insert("priority".to_string(), json!(priority.unwrap_or_default()));
+                RecordData::Uri(read(&data)?)
+            }
+            _ => return None,
+        };
+        Some(data)
+    }
+
+    /// Whether both describe the same record, ignoring the case of case insensitive fields such as hostnames and hex
+    /// strings. Other fields, e.g. the value of CAA or the target of URI records, are compared exactly.
+    pub fn equivalent(&self, other: &Self) -> bool {
+        self.clone().normalized() == other.clone().normalized()
+    }
+
+    /// The data with case insensitive fields such as hostnames and hex strings lowercased.
+    fn normalized(self) -> Self {
+        match self {
+            RecordData::Mx(mx) => RecordData::Mx(MxData {
+                exchange: normalize_hostname(&mx.exchange),
+                ..mx
+            }),
+            RecordData::Srv(srv) => RecordData::Srv(SrvData {
+                target: normalize_hostname(&srv.target),
+                ..srv
+            }),
+            RecordData::Caa(caa) => RecordData::Caa(CaaData {
+                tag: caa.tag.to_ascii_lowercase(),
+                ..caa
+            }),
+            RecordData::Https(svcb) => RecordData::Https(svcb.normalized()),
+            RecordData::Svcb(svcb) => RecordData::Svcb(svcb.normalized()),
+            RecordData::Tlsa(tlsa) => RecordData::Tlsa(TlsaData {
+                certificate: tlsa.certificate.to_ascii_lowercase(),
+                ..tlsa
+            }),
+            RecordData::Ds(ds) => RecordData::Ds(DsData {
+                digest: ds.digest.to_ascii_lowercase(),
+                ..ds
+            }),
+            RecordData::Sshfp(sshfp) => RecordData::Sshfp(SshfpData {
+                fingerprint: sshfp.fingerprint.to_ascii_lowercase(),
+                ..sshfp
+            }),
+            RecordData::Naptr(naptr) => RecordData::Naptr(NaptrData {
+                flags: naptr.flags.to_ascii_lowercase(),
+                service: naptr.service.to_ascii_lowercase(),
+                replacement: normalize_hostname(&naptr.replacement),
+                ..naptr
+            }),
+            data @ (RecordData::Cert(_) | RecordData::Uri(_) | RecordData::Loc(_)) => data,
+        }
+    }
+
+    /// The `content` of the record as cloudflare represents it.
+    pub fn cloudflare_content(&self) -> String {
+        match self {
+            RecordData::Mx(mx) => mx.exchange.clone(),
+            RecordData::Srv(srv) => format!("{} {} {}", srv.weight, srv.port, srv.target),
+            RecordData::Uri(uri) => format!("{} {}", uri.weight, quoted(&uri.target)),
+            data => data.to_string(),
+        }
+    }
+
+    /// The `priority` field of the cloudflare record.
+    pub fn cloudflare_priority(&self) -> Option<u16> {
+        match self {
+            RecordData::Mx(mx) => Some(mx.priority),
+            RecordData::Srv(srv) => Some(srv.priority),
+            RecordData::Uri(uri) => Some(uri.priority),
+            _ => None,
+        }
+    }
+
+    /// The `data` object of the cloudflare record.
+    pub fn cloudflare_data(&self) -> Option<Value> {
+        let data = match self {
+            RecordData::Mx(_) => return None,
+            RecordData::Srv(srv) => json!(srv),
+            RecordData::Caa(caa) => json!(caa),
+            RecordData::Https(svcb) | RecordData::Svcb(svcb) => json!(svcb),
+            RecordData::Tlsa(tlsa) => json!(tlsa),
+            RecordData::Ds(ds) => json!(ds),
+            RecordData::Sshfp(sshfp) => json!(sshfp),
+            RecordData::Naptr(naptr) => json!(naptr),
+            RecordData::Cert(cert) => json!(cert),
+            RecordData::Loc(loc) => json!(loc),
+            // the priority of URI records is a field of the record, not of the data
+            RecordData::Uri(uri) => json!({
+                "weight": uri.weight,
+                "target": uri.target,
+            }),
+        };
+        Some(data)
+    }
+}
+
+impl std::fmt::Display for RecordData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecordData::Mx(mx) => mx.fmt(f),
+            RecordData::Srv(srv) => srv.fmt(f),
+            RecordData::Caa(caa) => caa.fmt(f),
+            RecordData::Https(svcb) | RecordData::Svcb(svcb) => svcb.fmt(f),
+            RecordData::Tlsa(tlsa) => tlsa.fmt(f),
+            RecordData::Ds(ds) => ds.fmt(f),
+            RecordData::Sshfp(sshfp) => sshfp.fmt(f),
+            RecordData::Naptr(naptr) => naptr.fmt(f),
+            RecordData::Cert(cert) => cert.fmt(f),
+            RecordData::Uri(uri) => uri.fmt(f),
+            RecordData::Loc(loc) => loc.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_zone_format() {
+        let mx = RecordData::parse(RecordType::MX, "10 mail.example.com.")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            mx,
+            RecordData::Mx(MxData {
+                priority: 10,
+                exchange: "mail.example.com".to_string(),
+            })
+        );
+        assert_eq!(mx.to_string(), "10 mail.example.com");
+        assert_eq!(mx.cloudflare_content(), "mail.example.com");
+
+        let srv = RecordData::parse(RecordType::SRV, "10 5 5060 sip.example.com")
+            .unwrap()
+            .unwrap();
+        assert_eq!(srv.to_string(), "10 5 5060 sip.example.com");
+        assert_eq!(srv.cloudflare_content(), "5 5060 sip.example.com");
+        assert_eq!(srv.cloudflare_priority(), Some(10));
+        assert_eq!(
+            srv.cloudflare_data(),
+            Some(json!({"priority": 10, "weight": 5, "port": 5060, "target": "sip.example.com"}))
+        );
+
+        assert!(RecordData::parse(RecordType::A, "1.2.3.4").unwrap().is_none());
+        assert!(RecordData::parse(RecordType::MX, "mail.example.com").is_err());
+        assert!(RecordData::parse(RecordType::SRV, "10 5 99999 sip.example.com").is_err());
+    }
+
+    #[test]
+    fn parse_quoted_and_encoded_fields() {
+        let caa = RecordData::parse(
+            RecordType::CAA,
+            r#"0 issue "letsencrypt.org; validationmethods=dns-01""#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            caa.cloudflare_data(),
+            Some(json!({"flags": 0, "tag": "issue", "value": "letsencrypt.org; validationmethods=dns-01"}))
+        );
+        assert_eq!(
+            caa.to_string(),
+            r#"0 issue "letsencrypt.org; validationmethods=dns-01""#
+        );
+
+        let https = RecordData::parse(RecordType::HTTPS, r#"1 . alpn="h3,h2" ipv4hint="1.2.3.4""#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            https.cloudflare_data(),
+            Some(json!({"priority": 1, "target": ".", "value": r#"alpn="h3,h2" ipv4hint="1.2.3.4""#}))
+        );
+        let alias = RecordData::parse(RecordType::SVCB, "0 svc.example.com.")
+            .unwrap()
+            .unwrap();
+        assert_eq!(alias.to_string(), "0 svc.example.com");
+
+        let naptr = RecordData::parse(RecordType::NAPTR, r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com."#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(naptr.to_string(), r#"100 10 "S" "SIP+D2U" "" _sip._udp.example.com"#);
+
+        let sshfp = RecordData::parse(RecordType::SSHFP, "4 2 0123abcd").unwrap().unwrap();
+        assert_eq!(
+            sshfp.cloudflare_data(),
+            Some(json!({"algorithm": 4, "type": 2, "fingerprint": "0123abcd"}))
+        );
+        assert!(RecordData::parse(RecordType::TLSA, "3 1 1 not-hex").is_err());
+        assert!(RecordData::parse(RecordType::CAA, r#"0 issue "letsencrypt.org"#).is_err());
+
+        let uri = RecordData::parse(RecordType::URI, r#"10 1 "https://example.com/""#)
+            .unwrap()
+            .unwrap();
+        assert_eq!(uri.cloudflare_priority(), Some(10));
+        assert_eq!(
+            uri.cloudflare_data(),
+            Some(json!({"weight": 1, "target": "https://example.com/"}))
+        );
+    }
+
+    #[test]
+    fn equivalent_notations_normalize_equally() {
+        let same = |ty: RecordType, a: &str, b: &str| assert_eq!(normalize_content(ty, a), normalize_content(ty, b));
+
+        same(RecordType::AAAA, "2001:DB8:0:0:0:0:0:1", "2001:db8::1");
+        same(RecordType::CNAME, "Target.Example.com.", "target.example.com");
+        same(RecordType::TXT, r#""v=DKIM1; p=abc" "def""#, "v=DKIM1; p=abcdef");
+        same(RecordType::MX, "10 Mail.Example.com.", "10 mail.example.com");
+        same(RecordType::MX, "Mail.Example.com.", "mail.example.com");
+        same(
+            RecordType::CAA,
+            r#"0 ISSUE "letsencrypt.org""#,
+            "0 issue letsencrypt.org",
+        );
+        same(RecordType::DS, "2371 13 2 ABCDEF", "2371 13 2 abcdef");
+        same(
+            RecordType::HTTPS,
+            r#"1 . ipv4hint=1.2.3.4 ALPN="h3,h2""#,
+            r#"1 . alpn="h3,h2" ipv4hint="1.2.3.4""#,
+        );
+
+        assert_ne!(
+            normalize_content(RecordType::TXT, "Case Sensitive"),
+            normalize_content(RecordType::TXT, "case sensitive")
+        );
+    }
+
+    #[test]
+    fn parse_and_canonicalize_loc() {
+        let loc = RecordData::parse(RecordType::LOC, "52 31 12.0004 N 13 24 W 34.5m 15m")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            loc.to_string(),
+            "52 31 12.000 N 13 24 0.000 W 34.50m 10.00m 10000.00m 10.00m"
+        );
+        let RecordData::Loc(data) = &loc else {
+            panic!("expected LOC data");
+        };
+        assert_eq!(data.long_direction, LongitudeDirection::W);
+        assert_eq!(loc.cloudflare_data().unwrap()["precision_horz"], json!(10000.0));
+
+        assert_eq!(encode_loc_size(1.0), 0x12);
+        assert_eq!(encode_loc_size(10000.0), 0x16);
+        assert_eq!(decode_loc_size(0x13), 10.0);
+
+        assert!(RecordData::parse(RecordType::LOC, "91 0 0 N 13 24 36 E 0m").is_err());
+        assert!(RecordData::parse(RecordType::LOC, "52 31 12 X 13 24 36 E 0m").is_err());
+        assert!(RecordData::parse(RecordType::LOC, "52 31 12 N 13 24 36 E").is_err());
+    }
+}
@@ -1,22 +1,54 @@
+use crate::{
+    context::Context,
+    dns::{
+        cloudflare::RecordContent,
+        provider::DnsProvider,
+    },
+    record_data::{
+        CaaData,
+        CertData,
+        DsData,
+        LocData,
+        MxData,
+        NaptrData,
+        SrvData,
+        SshfpData,
+        SvcbData,
+        TlsaData,
+        UriData,
+    },
+};
 use k8s_openapi::{
     api::core::v1::{
         ConfigMap,
         Secret,
+        SecretKeySelector,
     },
     apimachinery::pkg::apis::meta::v1::Condition,
 };
-use kube::CustomResource;
+use kube::{
+    core::{
+        Message,
+        Rule,
+    },
+    CELSchema,
+    CustomResource,
+};
 use schemars::JsonSchema;
 use serde::{
     Deserialize,
     Serialize,
 };
+use std::{
+    collections::BTreeMap,
+    net::IpAddr,
+};
 
 /// Supported DNS record types.
 ///
 /// See https://developers.cloudflare.com/dns/manage-dns-records/reference/dns-record-types/#dns-record-types
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Default, Debug, PartialEq, Serialize, Deserialize, Clone, Copy, JsonSchema)]
+#[derive(Default, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, Clone, Copy, JsonSchema)]
 pub enum RecordType {
     #[default]
     #[serde(rename = "A")]
@@ -37,6 +69,53 @@ pub enum RecordType {
     SPF,
     #[serde(rename = "NS")]
     NS,
+    #[serde(rename = "PTR")]
+    PTR,
+    #[serde(rename = "CAA")]
+    CAA,
+    #[serde(rename = "HTTPS")]
+    HTTPS,
+    #[serde(rename = "SVCB")]
+    SVCB,
+    #[serde(rename = "TLSA")]
+    TLSA,
+    #[serde(rename = "DS")]
+    DS,
+    #[serde(rename = "SSHFP")]
+    SSHFP,
+    #[serde(rename = "NAPTR")]
+    NAPTR,
+    #[serde(rename = "CERT")]
+    CERT,
+    #[serde(rename = "URI")]
+    URI,
+}
+
+impl RecordType {
+    /// The record type as it is named by the cloudflare API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::AAAA => "AAAA",
+            RecordType::CNAME => "CNAME",
+            RecordType::MX => "MX",
+            RecordType::TXT => "TXT",
+            RecordType::SRV => "SRV",
+            RecordType::LOC => "LOC",
+            RecordType::SPF => "SPF",
+            RecordType::NS => "NS",
+            RecordType::PTR => "PTR",
+            RecordType::CAA => "CAA",
+            RecordType::HTTPS => "HTTPS",
+            RecordType::SVCB => "SVCB",
+            RecordType::TLSA => "TLSA",
+            RecordType::DS => "DS",
+            RecordType::SSHFP => "SSHFP",
+            RecordType::NAPTR => "NAPTR",
+            RecordType::CERT => "CERT",
+            RecordType::URI => "URI",
+        }
+    }
 }
 
 impl std::str::FromStr for RecordType {
@@ -53,13 +132,40 @@ impl std::str::FromStr for RecordType {
             "LOC" => Ok(RecordType::LOC),
             "SPF" => Ok(RecordType::SPF),
             "NS" => Ok(RecordType::NS),
+            "PTR" => Ok(RecordType::PTR),
+            "CAA" => Ok(RecordType::CAA),
+            "HTTPS" => Ok(RecordType::HTTPS),
+            "SVCB" => Ok(RecordType::SVCB),
+            "TLSA" => Ok(RecordType::TLSA),
+            "DS" => Ok(RecordType::DS),
+            "SSHFP" => Ok(RecordType::SSHFP),
+            "NAPTR" => Ok(RecordType::NAPTR),
+            "CERT" => Ok(RecordType::CERT),
+            "URI" => Ok(RecordType::URI),
             s => Err(eyre::eyre!("Invalid RecordType: {s:?}")),
         }
     }
 }
 
+/// Decides which existing cloudflare records with the same name and type a `CloudflareDNSRecord` may modify or delete.
+/// Records created by the operator carry a marker with the UID of the owning resource in their comment.
+#[derive(Default, Debug, PartialEq, Eq, Serialize, Deserialize, Clone, Copy, JsonSchema)]
+pub enum OwnershipPolicy {
+    /// Take over records without an owner marker (e.g. created manually) without ever deleting them, and never touch
+    /// records owned by another resource.
+    #[default]
+    #[serde(rename = "adopt")]
+    Adopt,
+    /// Take over any existing record, even if it is owned by another resource, and delete superfluous ones.
+    #[serde(rename = "takeover")]
+    Takeover,
+    /// Only modify records created by this resource. Fails if other records are in the way.
+    #[serde(rename = "refuse")]
+    Refuse,
+}
+
 /// [CustomResource] definition for a Cloudflare DNS record.
-#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, PartialEq, CELSchema)]
 #[kube(
     group = "dns.cloudflare.com",
     version = "v1alpha1",
@@ -67,52 +173,245 @@ impl std::str::FromStr for RecordType {
     status = "CloudflareDNSRecordStatus",
     namespaced
 )]
+#[cel_validate(rule = content_required_rule())]
+#[cel_validate(rule = structured_content_rule("mx", "MX"))]
+#[cel_validate(rule = structured_content_rule("srv", "SRV"))]
+#[cel_validate(rule = structured_content_rule("caa", "CAA"))]
+#[cel_validate(rule = structured_content_rule("https", "HTTPS"))]
+#[cel_validate(rule = structured_content_rule("svcb", "SVCB"))]
+#[cel_validate(rule = structured_content_rule("tlsa", "TLSA"))]
+#[cel_validate(rule = structured_content_rule("ds", "DS"))]
+#[cel_validate(rule = structured_content_rule("sshfp", "SSHFP"))]
+#[cel_validate(rule = structured_content_rule("naptr", "NAPTR"))]
+#[cel_validate(rule = structured_content_rule("cert", "CERT"))]
+#[cel_validate(rule = structured_content_rule("uri", "URI"))]
+#[cel_validate(rule = structured_content_rule("loc", "LOC"))]
 pub struct CloudflareDNSRecordSpec {
     /// The name of the record (e.g example.com)
     pub name: String,
-    /// The type of the record (e.g A, CNAME, MX, TXT, SRV, LOC, SPF, NS). Defaults to A.
+    /// The type of the record (e.g A, CNAME, MX, TXT, SRV, CAA, HTTPS, TLSA, PTR). Defaults to A. Contents of types
+    /// with several fields (MX, SRV, CAA, HTTPS, SVCB, TLSA, DS, SSHFP, NAPTR, CERT, URI, LOC) can be given
+    /// structured, e.g. as `mx` or `caa` content, or in zone file format, e.g. `10 mail.example.com`.
     #[serde(rename = "type")]
     pub ty: Option<RecordType>,
-    /// The content of the record such as an IP address or a service reference.
-    pub content: StringOrService,
+    /// Publish an A record for every ipv4 and an AAAA record for every ipv6 address of the content (e.g. all load
+    /// balancer IPs of a service) instead of records of a single `type`. Defaults to false.
+    pub dual_stack: Option<bool>,
+    /// The content of the record such as an IP address, a configMap / secret, service, ingress, gateway or node
+    /// reference, the public IP of the cluster, a template or structured record data such as `mx` or `caa`.
+    pub content: Option<StringOrService>,
+    /// Multiple contents for the record, e.g. for round-robin A records or several MX entries. One cloudflare record
+    /// is created per content. Can be combined with `content`.
+    pub contents: Option<Vec<StringOrService>>,
     /// TTL in seconds
     pub ttl: Option<i64>,
     /// Whether the record is proxied by Cloudflare
     pub proxied: Option<bool>,
-    /// Arbitrary comment
+    /// Arbitrary comment. Cut off to fit cloudflare's limit of 100 characters together with the ownership marker.
     pub comment: Option<String>,
     /// Tags to apply to the record
     pub tags: Option<Vec<String>>,
     /// The cloudflare zone ID to create the record in
     pub zone: ZoneNameOrId,
+    /// The name of a `CloudflareCredentials` resource in the namespace of the record to access cloudflare with.
+    /// Defaults to the API token of the operator.
+    pub credentials: Option<String>,
+    /// How to deal with existing records that were not created by this resource (adopt, takeover or refuse). Defaults
+    /// to adopt.
+    pub ownership: Option<OwnershipPolicy>,
+}
+
+/// [CustomResource] definition for cloudflare API credentials, e.g. of a team owning zones in its own cloudflare
+/// account. `CloudflareDNSRecord`s of the same namespace use them by setting `credentials`.
+#[derive(CustomResource, Deserialize, Serialize, Clone, Debug, PartialEq, JsonSchema)]
+#[kube(
+    group = "dns.cloudflare.com",
+    version = "v1alpha1",
+    kind = "CloudflareCredentials",
+    plural = "cloudflarecredentials",
+    namespaced
+)]
+pub struct CloudflareCredentialsSpec {
+    /// The secret key holding the cloudflare API token
+    pub api_token: SecretKeySelector,
+    /// Only use zones of this cloudflare account, e.g. if the token has access to several accounts
+    pub account_id: Option<String>,
+    /// Names or IDs of the zones records may be managed in with these credentials. Defaults to all zones of the token.
+    pub allowed_zones: Option<Vec<String>>,
+}
+
+impl CloudflareCredentialsSpec {
+    /// The API token, read from the secret in namespace `ns`.
+    pub async fn lookup_api_token(&self, client: &kube::Client, ns: &str) -> eyre::Result<Option<String>> {
+        Reference::Secret(self.api_token.clone()).lookup(client, ns).await
+    }
+}
+
+/// CEL rule requiring `content` or `contents`, so that a record without content is rejected when it is applied.
+fn content_required_rule() -> Rule {
+    Rule::new("has(self.content) || has(self.contents)")
+        .message(Message::Message("content or contents is required".to_string()))
+}
+
+/// CEL rule admitting structured `variant` contents only for records of type `ty`.
+fn structured_content_rule(variant: &str, ty: &str) -> Rule {
+    let matches_type = format!("(has(self.type) && self.type == '{ty}')");
+    Rule::new(format!(
+        "(!has(self.content) || !has(self.content.{variant}) || {matches_type}) && \
+         (!has(self.contents) || self.contents.all(c, !has(c.{variant})) || {matches_type})"
+    ))
+    .message(Message::Message(format!("{variant} contents require type {ty}")))
 }
 
 impl CloudflareDNSRecordSpec {
-    /// If set directly to a value, return that, otherwise look up the service and return the IP.
-    pub async fn lookup_content(&self, client: &kube::Client, ns: &str) -> eyre::Result<Option<String>> {
-        match &self.content {
-            StringOrService::Value(value) => Ok(Some(value.clone())),
-            StringOrService::Service(selector) => {
-                let ns = selector.namespace.as_deref().unwrap_or(ns);
-                let name = selector.name.as_str();
-                let record_type = self.ty;
-                let Some(ip) = crate::services::public_ip_from_service(client, name, ns, record_type).await? else {
-                    error!("no public ip found for service {ns}/{name}");
-                    return Ok(None);
+    /// All content sources of the record, `content` first.
+    pub fn content_sources(&self) -> impl Iterator<Item = &StringOrService> {
+        self.content.iter().chain(self.contents.iter().flatten())
+    }
+
+    /// Resolves all contents of the record: values are returned directly, for services the IP is looked up. Load
+    /// balancer hostnames are published as CNAME. Returns `None` if any of the contents cannot be resolved, as
+    /// publishing only some of them would remove the others.
+    pub async fn lookup_contents(&self, ctx: &Context, ns: &str) -> eyre::Result<Option<Vec<RecordContent>>> {
+        let dual_stack = self.dual_stack.unwrap_or(false);
+        let hint = if dual_stack { None } else { self.ty };
+
+        let mut contents = Vec::new();
+        for source in self.content_sources() {
+            let Some(resolved) = source.lookup(ctx, ns, hint, dual_stack).await? else {
+                return Ok(None);
+            };
+            for content in resolved {
+                let content = match content {
+                    ResolvedContent::Alias(hostname) => RecordContent::new(RecordType::CNAME, hostname),
+                    ResolvedContent::Value(content) if dual_stack => {
+                        let ip = content
+                            .parse::<IpAddr>()
+                            .map_err(|_| eyre::eyre!("dual stack records require ip addresses, got {content:?}"))?;
+                        let ty = if ip.is_ipv4() { RecordType::A } else { RecordType::AAAA };
+                        RecordContent::new(ty, ip)
+                    }
+                    ResolvedContent::Value(content) => RecordContent::parse(self.ty.unwrap_or_default(), &content)?,
                 };
-                Ok(Some(ip.to_string()))
+                if !contents.contains(&content) {
+                    contents.push(content);
+                }
             }
         }
+
+        let is_cname = |content: &RecordContent| content.record_type == RecordType::CNAME;
+        if contents.len() > 1 && contents.iter().any(is_cname) {
+            eyre::bail!("a CNAME record cannot be combined with other contents: {contents:?}");
+        }
+
+        Ok(Some(contents))
+    }
+
+    /// All names the record is published under: `name` and, for gateway contents with
+    /// [`GatewaySelector::hostnames_from_routes`], the hostnames of the HTTPRoutes attached to the gateway that lie
+    /// within the zone `zone_id` of `provider`.
+    pub async fn lookup_names(
+        &self,
+        ctx: &Context,
+        provider: &dyn DnsProvider,
+        ns: &str,
+        zone_id: &str,
+    ) -> eyre::Result<Vec<String>> {
+        let mut names = vec![self.name.clone()];
+        let mut zone_name = None;
+        for source in self.content_sources() {
+            let StringOrService::Gateway(selector) = source else {
+                continue;
+            };
+            if !selector.hostnames_from_routes.unwrap_or(false) {
+                continue;
+            }
+            if zone_name.is_none() {
+                let zones = provider.list_zones().await?;
+                let zone = zones.into_iter().find(|zone| zone.id == zone_id);
+                zone_name = Some(zone.ok_or_else(|| eyre::eyre!("zone {zone_id} not found"))?.name);
+            }
+            let zone_name = zone_name.as_deref().expect("zone name");
+            for hostname in crate::gateways::route_hostnames(ctx, selector, ns, zone_name).await? {
+                if !names.contains(&hostname) {
+                    names.push(hostname);
+                }
+            }
+        }
+        Ok(names)
     }
 }
 
+impl StringOrService {
+    /// If set directly to a value (or a template), return that. Values in a configMap or secret are read, for a
+    /// service, ingress, gateway, nodes or the public IP the IP(s) or hostname are looked up. With `all_ips`, all
+    /// IPs are returned regardless of the `all_ips` setting of the selector.
+    pub async fn lookup(
+        &self,
+        ctx: &Context,
+        ns: &str,
+        record_type: Option<RecordType>,
+        all_ips: bool,
+    ) -> eyre::Result<Option<Vec<ResolvedContent>>> {
+        let client = &ctx.client;
+        match self {
+            StringOrService::Value(value) => Ok(Some(vec![ResolvedContent::Value(value.clone())])),
+            StringOrService::Mx(mx) => Ok(Some(vec![ResolvedContent::Value(mx.to_string())])),
+            StringOrService::Srv(srv) => Ok(Some(vec![ResolvedContent::Value(srv.to_string())])),
+            StringOrService::Caa(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Https(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Svcb(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Tlsa(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Ds(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Sshfp(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Naptr(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Cert(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Uri(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Loc(data) => Ok(Some(vec![ResolvedContent::Value(data.to_string())])),
+            StringOrService::Service(selector) => {
+                crate::services::lookup_service(client, selector, ns, record_type, all_ips).await
+            }
+            StringOrService::Ingress(selector) => {
+                crate::ingresses::lookup_ingress(client, selector, ns, record_type, all_ips).await
+            }
+            StringOrService::Gateway(selector) => {
+                crate::gateways::lookup_gateway(client, selector, ns, record_type, all_ips).await
+            }
+            StringOrService::Node(selector) => crate::nodes::lookup_nodes(client, selector, record_type, all_ips).await,
+            StringOrService::PublicIp(_) => crate::public_ip::lookup_public_ip(&ctx.public_ip, record_type).await,
+            StringOrService::Reference(reference) => Ok(reference
+                .lookup(client, ns)
+                .await?
+                .map(|value| vec![ResolvedContent::Value(value)])),
+            StringOrService::Template(template) => Ok(template
+                .render(ctx, ns)
+                .await?
+                .map(|rendered| vec![ResolvedContent::Value(rendered)])),
+        }
+    }
+}
+
+/// A resolved content of a [`StringOrService`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ResolvedContent {
+    /// A value such as an IP address, published with the type of the record.
+    Value(String),
+    /// A hostname that can only be published as CNAME, e.g. of a load balancer.
+    Alias(String),
+}
+
 /// Status of a Cloudflare DNS record.
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub struct CloudflareDNSRecordStatus {
-    /// The ID of the cloudflare record
+    /// The ID of the (first) cloudflare record
     pub record_id: String,
+    /// All cloudflare records managed by this resource
+    pub records: Option<Vec<ManagedRecord>>,
     /// The zone ID of the record
     pub zone_id: String,
+    /// The `CloudflareCredentials` the records were created with, none if they were created with the token of the
+    /// operator. Records are deleted with the same credentials, even if `spec.credentials` changed in the meantime.
+    pub credentials: Option<String>,
     /// Whether we are able to resolve the DNS record (false) or not (true). If no dns check is performed, this field
     /// will default to true.
     pub pending: bool,
@@ -120,6 +419,20 @@ pub struct CloudflareDNSRecordStatus {
     pub conditions: Option<Vec<Condition>>,
 }
 
+/// A cloudflare record created for a content of a `CloudflareDNSRecord`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ManagedRecord {
+    /// The ID of the cloudflare record
+    pub id: String,
+    /// The name of the record
+    pub name: Option<String>,
+    /// The type of the record
+    #[serde(rename = "type")]
+    pub record_type: String,
+    /// The content of the record
+    pub content: String,
+}
+
 /// A Cloudflare DNS Zone. Can either be a name (such as example.com) or id.
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum ZoneNameOrId {
@@ -135,6 +448,54 @@ pub enum StringOrService {
     Value(String),
     #[serde(rename = "service")]
     Service(ServiceSelector),
+    #[serde(rename = "ingress")]
+    Ingress(IngressSelector),
+    #[serde(rename = "gateway")]
+    Gateway(GatewaySelector),
+    #[serde(rename = "node")]
+    Node(NodeSelector),
+    #[serde(rename = "publicIp")]
+    PublicIp(PublicIpSelector),
+    #[serde(rename = "template")]
+    Template(TemplateContent),
+    #[serde(rename = "from")]
+    Reference(Reference),
+    /// Structured MX data, requires type MX.
+    #[serde(rename = "mx")]
+    Mx(MxData),
+    /// Structured SRV data, requires type SRV.
+    #[serde(rename = "srv")]
+    Srv(SrvData),
+    /// Structured CAA data, requires type CAA.
+    #[serde(rename = "caa")]
+    Caa(CaaData),
+    /// Structured HTTPS data, requires type HTTPS.
+    #[serde(rename = "https")]
+    Https(SvcbData),
+    /// Structured SVCB data, requires type SVCB.
+    #[serde(rename = "svcb")]
+    Svcb(SvcbData),
+    /// Structured TLSA data, requires type TLSA.
+    #[serde(rename = "tlsa")]
+    Tlsa(TlsaData),
+    /// Structured DS data, requires type DS.
+    #[serde(rename = "ds")]
+    Ds(DsData),
+    /// Structured SSHFP data, requires type SSHFP.
+    #[serde(rename = "sshfp")]
+    Sshfp(SshfpData),
+    /// Structured NAPTR data, requires type NAPTR.
+    #[serde(rename = "naptr")]
+    Naptr(NaptrData),
+    /// Structured CERT data, requires type CERT.
+    #[serde(rename = "cert")]
+    Cert(CertData),
+    /// Structured URI data, requires type URI.
+    #[serde(rename = "uri")]
+    Uri(UriData),
+    /// Structured LOC data, requires type LOC.
+    #[serde(rename = "loc")]
+    Loc(LocData),
 }
 
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
@@ -143,6 +504,70 @@ pub struct ServiceSelector {
     pub name: String,
     /// Namespace, default is the same namespace as the referent.
     pub namespace: Option<String>,
+    /// Publish every load balancer / external IP of the service matching the record type (ipv4 for A, ipv6 for AAAA)
+    /// as a separate record instead of a single one. Defaults to false.
+    pub all_ips: Option<bool>,
+    /// If the load balancer only provides a hostname (e.g. AWS ELBs), resolve it and publish its addresses as A/AAAA
+    /// records instead of publishing a CNAME to it. Defaults to false.
+    pub flatten_hostname: Option<bool>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct IngressSelector {
+    /// Ingress name
+    pub name: String,
+    /// Namespace, default is the same namespace as the referent.
+    pub namespace: Option<String>,
+    /// Publish every load balancer IP of the ingress matching the record type (ipv4 for A, ipv6 for AAAA) as a
+    /// separate record instead of a single one. Defaults to false.
+    pub all_ips: Option<bool>,
+    /// If the load balancer only provides a hostname, resolve it and publish its addresses as A/AAAA records instead
+    /// of publishing a CNAME to it. Defaults to false.
+    pub flatten_hostname: Option<bool>,
+}
+
+/// Reference to a Gateway API `Gateway` (gateway.networking.k8s.io). Requires the controller to run with
+/// `--gateway-api`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct GatewaySelector {
+    /// Gateway name
+    pub name: String,
+    /// Namespace, default is the same namespace as the referent.
+    pub namespace: Option<String>,
+    /// Publish every IP address of the gateway matching the record type (ipv4 for A, ipv6 for AAAA) as a separate
+    /// record instead of a single one. Defaults to false.
+    pub all_ips: Option<bool>,
+    /// If the gateway only provides a hostname address, resolve it and publish its addresses as A/AAAA records
+    /// instead of publishing a CNAME to it. Defaults to false.
+    pub flatten_hostname: Option<bool>,
+    /// Additionally publish the record under every hostname of the HTTPRoutes attached to the gateway and accepted by
+    /// its listeners. Hostnames outside of the zone and wildcards are skipped. Defaults to false.
+    pub hostnames_from_routes: Option<bool>,
+}
+
+/// Selects nodes whose addresses are published, e.g. for workloads using `hostNetwork` or NodePort services. Every
+/// ready node contributes its `ExternalIP` addresses, or its `InternalIP` addresses if it has no external ones.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct NodeSelector {
+    /// Label selector for the nodes (e.g. `node-role.kubernetes.io/edge=true`), default is all nodes.
+    pub selector: Option<String>,
+    /// Publish the addresses of all matching nodes as separate records. Set to false to publish a single address
+    /// only. Defaults to true.
+    pub all_ips: Option<bool>,
+}
+
+/// Publishes the public (egress) IP of the cluster, as discovered by the controller (see `--public-ip-source`).
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PublicIpSelector {}
+
+/// A content rendered from a template such as `v=spf1 ip4:{{ service.traefik.ip }} -all`. Placeholders can refer to
+/// `values.<key>` or to an address of a service with `service.[<namespace>.]<name>.<ip|ipv4|ipv6|hostname>`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct TemplateContent {
+    /// The template
+    pub template: String,
+    /// Values available as `values.<key>` in the template, set directly or read from a configMap or secret.
+    pub values: Option<BTreeMap<String, ValueOrReference>>,
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
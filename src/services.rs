@@ -1,23 +1,100 @@
-use crate::resources::{
-    CloudflareDNSRecord,
-    RecordType,
+use crate::{
+    dependencies::{
+        Dependency,
+        DependencyIndex,
+    },
+    resources::{
+        CloudflareDNSRecord,
+        RecordType,
+        ResolvedContent,
+        ServiceSelector,
+    },
+};
+use eyre::{
+    Context as _,
+    Result,
 };
-use eyre::Result;
 use k8s_openapi::api::core::v1::Service;
 use kube::runtime::reflector::ObjectRef;
 use std::net::IpAddr;
 
-/// Checks if a [`Service`] resource is suitable for providing a public IP address.
-pub fn is_suitable_service(svc: Service) -> Option<ObjectRef<CloudflareDNSRecord>> {
-    let spec = svc.spec.as_ref()?;
-    if spec.type_.as_deref() == Some("LoadBalancer") || spec.external_ips.as_ref().map_or(false, |ips| !ips.is_empty())
-    {
-        let name = svc.metadata.name.as_deref()?;
-        let ns = svc.metadata.namespace.as_deref()?;
-        Some(ObjectRef::new(name).within(ns))
+/// Maps a change of a [`Service`] to all `CloudflareDNSRecord`s referencing it.
+pub fn records_referencing_service(index: &DependencyIndex, svc: Service) -> Vec<ObjectRef<CloudflareDNSRecord>> {
+    let (Some(name), Some(namespace)) = (svc.metadata.name, svc.metadata.namespace) else {
+        return Vec::new();
+    };
+    index.dependents(&Dependency::Service { name, namespace })
+}
+
+/// The addresses a [`Service`] (or an `Ingress`) is reachable at from the outside.
+#[derive(Debug, Default)]
+pub struct ServiceAddresses {
+    pub ips: Vec<IpAddr>,
+    /// Load balancer ingress hostnames, e.g. of AWS ELBs.
+    pub hostnames: Vec<String>,
+}
+
+/// Resolves the content a [`ServiceSelector`] refers to. Services whose load balancer only provides a hostname are
+/// published as CNAME, or as the addresses the hostname resolves to if [`ServiceSelector::flatten_hostname`] is set.
+pub async fn lookup_service(
+    client: &kube::Client,
+    selector: &ServiceSelector,
+    ns: &str,
+    record_type: Option<RecordType>,
+    all_ips: bool,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let ns = selector.namespace.as_deref().unwrap_or(ns);
+    let name = selector.name.as_str();
+    let all_ips = all_ips || selector.all_ips.unwrap_or(false);
+
+    let Some(addresses) = service_addresses(client, name, ns).await? else {
+        error!("no public ip found for service {ns}/{name}");
+        return Ok(None);
+    };
+
+    let flatten_hostname = selector.flatten_hostname.unwrap_or(false);
+    resolve_addresses(addresses, record_type, all_ips, flatten_hostname, name, ns).await
+}
+
+/// Turns the addresses of a load balanced resource (a [`Service`] or an `Ingress`) into record contents. A load
+/// balancer that only provides a hostname is published as alias, unless `flatten_hostname` is set.
+pub(crate) async fn resolve_addresses(
+    addresses: ServiceAddresses,
+    record_type: Option<RecordType>,
+    all_ips: bool,
+    flatten_hostname: bool,
+    name: &str,
+    ns: &str,
+) -> Result<Option<Vec<ResolvedContent>>> {
+    let ips = match &addresses.hostnames[..] {
+        [hostname, rest @ ..] if addresses.ips.is_empty() => {
+            if !rest.is_empty() {
+                warn!("{ns}/{name} has multiple load balancer hostnames, using {hostname:?}");
+            }
+            if !flatten_hostname {
+                return Ok(Some(vec![ResolvedContent::Alias(hostname.clone())]));
+            }
+            resolve_hostname(hostname).await?
+        }
+        _ => addresses.ips,
+    };
+
+    let ips = if all_ips {
+        filter_ips(ips, record_type)
     } else {
-        None
+        select_ip(ips, record_type, name, ns).into_iter().collect()
+    };
+
+    if ips.is_empty() {
+        error!("no public ip found for {ns}/{name}");
+        return Ok(None);
     }
+
+    Ok(Some(
+        ips.into_iter()
+            .map(|ip| ResolvedContent::Value(ip.to_string()))
+            .collect(),
+    ))
 }
 
 /// Extracts the public IP address from a [`Service`] resource.
@@ -27,6 +104,14 @@ pub async fn public_ip_from_service(
     ns: &str,
     record_type: Option<RecordType>,
 ) -> Result<Option<IpAddr>> {
+    let Some(addresses) = service_addresses(client, name, ns).await? else {
+        return Ok(None);
+    };
+    Ok(select_ip(addresses.ips, record_type, name, ns))
+}
+
+/// Extracts the load balancer ingress addresses or external IPs from a [`Service`] resource.
+pub async fn service_addresses(client: &kube::Client, name: &str, ns: &str) -> Result<Option<ServiceAddresses>> {
     let svc = kube::api::Api::<Service>::namespaced(client.clone(), ns)
         .get(name)
         .await?;
@@ -37,24 +122,25 @@ pub async fn public_ip_from_service(
     };
 
     if spec.type_.as_deref() == Some("LoadBalancer") {
-        let Some(ips) = svc.status.as_ref().and_then(|s| {
-            s.load_balancer
-                .as_ref()
-                .and_then(|lb| lb.ingress.as_ref())
-                .map(|ingress| {
-                    ingress
-                        .iter()
-                        .filter_map(|i| {
-                            let ip = i.ip.as_deref()?;
-                            ip.parse::<IpAddr>().ok()
-                        })
-                        .collect::<Vec<_>>()
-                })
-        }) else {
+        let Some(ingress) = svc
+            .status
+            .as_ref()
+            .and_then(|s| s.load_balancer.as_ref())
+            .and_then(|lb| lb.ingress.as_ref())
+        else {
             return Err(eyre::eyre!("no load balancer ip found"));
         };
 
-        return Ok(select_ip(ips, record_type, name, ns));
+        return Ok(Some(ServiceAddresses {
+            ips: ingress
+                .iter()
+                .filter_map(|i| {
+                    let ip = i.ip.as_deref()?;
+                    ip.parse::<IpAddr>().ok()
+                })
+                .collect(),
+            hostnames: ingress.iter().filter_map(|i| i.hostname.clone()).collect(),
+        }));
     }
 
     if let Some(ips) = spec.external_ips.as_ref().map(|ips| {
@@ -62,18 +148,44 @@ pub async fn public_ip_from_service(
             .filter_map(|ip| ip.parse::<IpAddr>().ok())
             .collect::<Vec<_>>()
     }) {
-        return Ok(select_ip(ips, record_type, name, ns));
+        return Ok(Some(ServiceAddresses {
+            ips,
+            hostnames: Vec::new(),
+        }));
     };
 
     warn!("Service {ns}/{name} is not a LoadBalancer and has no external IPs");
     Ok(None)
 }
 
+/// Resolves a load balancer hostname to its addresses using the system resolver.
+async fn resolve_hostname(hostname: &str) -> Result<Vec<IpAddr>> {
+    let addrs = tokio::net::lookup_host((hostname, 0))
+        .await
+        .with_context(|| format!("unable to resolve load balancer hostname {hostname:?}"))?;
+    let mut ips = Vec::new();
+    for addr in addrs {
+        if !ips.contains(&addr.ip()) {
+            ips.push(addr.ip());
+        }
+    }
+    Ok(ips)
+}
+
+/// Keeps the IPs matching the address family of `record_type`. Without a hint about the family, all IPs are kept.
+pub(crate) fn filter_ips(ips: Vec<IpAddr>, record_type: Option<RecordType>) -> Vec<IpAddr> {
+    match record_type {
+        Some(RecordType::A) => ips.into_iter().filter(IpAddr::is_ipv4).collect(),
+        Some(RecordType::AAAA) => ips.into_iter().filter(IpAddr::is_ipv6).collect(),
+        _ => ips,
+    }
+}
+
 /// Selects the most suitable IP address from a list of IPs, given a [hint](RecordType) about the expected record type.
 fn select_ip(ips: Vec<IpAddr>, record_type: Option<RecordType>, name: &str, ns: &str) -> Option<IpAddr> {
     match (&ips[..], record_type) {
-        ([], None) => {
-            warn!("Service {ns}/{name} has no lb/external ip");
+        ([], _) => {
+            warn!("{ns}/{name} has no lb/external ip");
             None
         }
 
@@ -100,8 +212,28 @@ fn select_ip(ips: Vec<IpAddr>, record_type: Option<RecordType>, name: &str, ns:
 
         // No uselful hint
         (ips, _) => {
-            warn!("Service {ns}/{name} has multiple load balancer ips, using the first ipv4 one or the first one if none are ipv4");
+            warn!("{ns}/{name} has multiple load balancer ips, using the first ipv4 one or the first one if none are ipv4");
             Some(ips.iter().find(|ip| ip.is_ipv4()).copied().unwrap_or(ips[0]))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_ips_by_record_type() {
+        let ips = ["1.2.3.4", "::1", "5.6.7.8"]
+            .map(|ip| ip.parse::<IpAddr>().unwrap())
+            .to_vec();
+
+        let v4 = filter_ips(ips.clone(), Some(RecordType::A));
+        assert_eq!(v4, [ips[0], ips[2]]);
+
+        let v6 = filter_ips(ips.clone(), Some(RecordType::AAAA));
+        assert_eq!(v6, [ips[1]]);
+
+        assert_eq!(filter_ips(ips.clone(), None), ips);
+    }
+}
@@ -0,0 +1,192 @@
+//! Template contents such as `v=spf1 ip4:{{ service.traefik.ip }} -all`. Placeholders are enclosed in `{{ }}` and
+//! can be
+//!
+//! - `values.<key>`: an entry of the `values` of the template, a value or a configMap / secret reference
+//! - `service.<name>.<field>` or `service.<namespace>.<name>.<field>`: an address of a service, where field is `ip`
+//!   (preferring ipv4), `ipv4`, `ipv6` or `hostname`
+
+use crate::{
+    context::Context,
+    dependencies::Dependency,
+    resources::TemplateContent,
+    services::service_addresses,
+};
+use eyre::{
+    bail,
+    Result,
+};
+
+/// A parsed piece of a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment<'a> {
+    Literal(&'a str),
+    Value(&'a str),
+    Service {
+        name: &'a str,
+        namespace: Option<&'a str>,
+        field: ServiceField,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ServiceField {
+    Ip,
+    Ipv4,
+    Ipv6,
+    Hostname,
+}
+
+fn parse(template: &str) -> Result<Vec<Segment<'_>>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if start > 0 {
+            segments.push(Segment::Literal(&rest[..start]));
+        }
+        let Some(end) = rest[start..].find("}}") else {
+            bail!("unclosed placeholder in template {template:?}");
+        };
+        let expression = rest[start + 2..start + end].trim();
+        segments.push(parse_placeholder(expression)?);
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest));
+    }
+
+    Ok(segments)
+}
+
+fn parse_placeholder(expression: &str) -> Result<Segment<'_>> {
+    let parts = expression.split('.').collect::<Vec<_>>();
+    let field = |field: &str| match field {
+        "ip" => Ok(ServiceField::Ip),
+        "ipv4" => Ok(ServiceField::Ipv4),
+        "ipv6" => Ok(ServiceField::Ipv6),
+        "hostname" => Ok(ServiceField::Hostname),
+        field => Err(eyre::eyre!(
+            "unknown service field {field:?} in placeholder {expression:?}"
+        )),
+    };
+
+    match parts[..] {
+        ["values", key] => Ok(Segment::Value(key)),
+        ["service", name, f] => Ok(Segment::Service {
+            name,
+            namespace: None,
+            field: field(f)?,
+        }),
+        ["service", namespace, name, f] => Ok(Segment::Service {
+            name,
+            namespace: Some(namespace),
+            field: field(f)?,
+        }),
+        _ => bail!("invalid placeholder {expression:?}, expected values.<key> or service.[<namespace>.]<name>.<field>"),
+    }
+}
+
+impl TemplateContent {
+    /// The resources the template reads from. Invalid templates have no dependencies.
+    pub fn dependencies(&self, ns: &str) -> Vec<Dependency> {
+        let mut dependencies = Vec::new();
+        for segment in parse(&self.template).unwrap_or_default() {
+            let dependency = match segment {
+                Segment::Literal(_) => continue,
+                Segment::Value(key) => {
+                    let Some(value) = self.values.as_ref().and_then(|values| values.get(key)) else {
+                        continue;
+                    };
+                    let Some(dependency) = Dependency::of_value(value, ns) else {
+                        continue;
+                    };
+                    dependency
+                }
+                Segment::Service { name, namespace, .. } => Dependency::Service {
+                    name: name.to_string(),
+                    namespace: namespace.unwrap_or(ns).to_string(),
+                },
+            };
+            if !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+        }
+        dependencies
+    }
+
+    /// Renders the template. Returns `None` if any of the placeholders cannot be resolved.
+    pub async fn render(&self, ctx: &Context, ns: &str) -> Result<Option<String>> {
+        let mut rendered = String::new();
+
+        for segment in parse(&self.template)? {
+            let value = match segment {
+                Segment::Literal(literal) => Some(literal.to_string()),
+                Segment::Value(key) => {
+                    let Some(value) = self.values.as_ref().and_then(|values| values.get(key)) else {
+                        bail!("template value {key:?} is not defined");
+                    };
+                    value.lookup(&ctx.client, ns).await?
+                }
+                Segment::Service { name, namespace, field } => {
+                    let ns = namespace.unwrap_or(ns);
+                    let addresses = service_addresses(&ctx.client, name, ns).await?.unwrap_or_default();
+                    let ips = &addresses.ips;
+                    match field {
+                        ServiceField::Ip => ips
+                            .iter()
+                            .find(|ip| ip.is_ipv4())
+                            .or(ips.first())
+                            .map(ToString::to_string),
+                        ServiceField::Ipv4 => ips.iter().find(|ip| ip.is_ipv4()).map(ToString::to_string),
+                        ServiceField::Ipv6 => ips.iter().find(|ip| ip.is_ipv6()).map(ToString::to_string),
+                        ServiceField::Hostname => addresses.hostnames.first().cloned(),
+                    }
+                }
+            };
+
+            let Some(value) = value else {
+                error!("unable to resolve placeholder in template {:?}", self.template);
+                return Ok(None);
+            };
+            rendered.push_str(&value);
+        }
+
+        Ok(Some(rendered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_templates() {
+        assert_eq!(
+            parse("v=spf1 ip4:{{ service.traefik.ip }} ip6:{{service.ingress.traefik.ipv6}} -all").unwrap(),
+            [
+                Segment::Literal("v=spf1 ip4:"),
+                Segment::Service {
+                    name: "traefik",
+                    namespace: None,
+                    field: ServiceField::Ip,
+                },
+                Segment::Literal(" ip6:"),
+                Segment::Service {
+                    name: "traefik",
+                    namespace: Some("ingress"),
+                    field: ServiceField::Ipv6,
+                },
+                Segment::Literal(" -all"),
+            ]
+        );
+        assert_eq!(
+            parse("v=DKIM1; k=rsa; p={{ values.key }}").unwrap(),
+            [Segment::Literal("v=DKIM1; k=rsa; p="), Segment::Value("key")]
+        );
+        assert_eq!(parse("plain").unwrap(), [Segment::Literal("plain")]);
+
+        assert!(parse("{{ values.key ").is_err());
+        assert!(parse("{{ service.traefik.port }}").is_err());
+        assert!(parse("{{ foo }}").is_err());
+    }
+}